@@ -1,57 +1,195 @@
+// Passes pick whichever traversal suits them, so not every one of these is in use yet
+#[allow(dead_code)]
+pub mod fold;
+pub mod visit;
+#[allow(dead_code)]
+pub mod visit_mut;
+
 pub use crate::Ident;
 
 use std::fmt::{Debug, Formatter, Result as FmtResult};
 
+/// A byte range into the source file, `start` is inclusive and `end` is exclusive
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub const fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Item {
     Func(FuncDef),
     Module(Module),
+    Usage(Usage),
+    Type(TypeDef),
+    /// An item that failed to parse
+    Error(Span),
+}
+
+impl Item {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Func(func) => func.span,
+            Self::Module(module) => module.span,
+            Self::Usage(usage) => usage.span,
+            Self::Type(def) => def.span,
+            Self::Error(span) => *span,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct FuncDef {
+    pub docs: Vec<String>,
     pub name: Ident,
     pub params: Vec<(Pattern, Type)>,
     pub ret: Option<Type>,
     pub body: Vec<Expr>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Module {
+    pub docs: Vec<String>,
     pub name: Ident,
     pub items: Vec<Item>,
+    pub span: Span,
+}
+
+/// `type Name = ...`, the declaration of a nominal type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeDef {
+    pub docs: Vec<String>,
+    pub name: Ident,
+    pub kind: TypeDefKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeDefKind {
+    /// `{ field: Type, ... }`
+    Record(Vec<FieldDef>),
+    /// `| Variant Type ... | ...`
+    Variants(Vec<VariantDef>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldDef {
+    pub name: Ident,
+    pub ty: Type,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariantDef {
+    pub name: Ident,
+    pub fields: Vec<Type>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Type {
+    pub kind: TypeKind,
+    pub span: Span,
+}
+
+impl Type {
+    pub const fn new(kind: TypeKind, span: Span) -> Self {
+        Self { kind, span }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub enum Type {
+pub enum TypeKind {
     Path(Path),
     Bool,
     Int,
     String,
+    Tuple(Vec<Type>),
+    /// `List elem`
+    List(Box<Type>),
+    /// `fn(args) -> ret`, the type of functions and lambdas
+    Func(Vec<Type>, Box<Type>),
+    Generic(Ident),
+    Refined(Box<Refinement>),
+}
+
+/// The type `{ binder: ty => predicate }`, the values of `ty` for which `predicate` holds
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Refinement {
+    pub binder: Ident,
+    pub ty: Type,
+    pub predicate: Expr,
 }
 
 #[derive(Clone, PartialEq, Eq, Hash)]
-pub enum Expr {
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+impl Expr {
+    pub const fn new(kind: ExprKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+impl Debug for Expr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        Debug::fmt(&self.kind, f)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub enum ExprKind {
     Let(Box<Let>),
     Ensure(Box<Ensure>),
-    // Match(Match),
+    Match(Box<Match>),
+    If(Box<If>),
     // Return(Return),
     Literal(Literal),
     Var(Ident),
     App(Box<App>),
-    // Not(Not),
+    Binary(Box<Binary>),
+    Unary(Box<Unary>),
+    Ascribed(Box<Ascription>),
+    Lambda(Box<Lambda>),
     Paren(Box<Expr>),
+    Tuple(Vec<Expr>),
+    /// `[elems, ...]`
+    List(Vec<Expr>),
+    Record(Box<Record>),
+    Field(Box<FieldAccess>),
+    /// An expression that failed to parse
+    Error,
 }
 
-impl Debug for Expr {
+impl Debug for ExprKind {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         match self {
             Self::Let(let_binding) => Debug::fmt(let_binding, f),
             Self::Ensure(ensure) => Debug::fmt(ensure, f),
+            Self::Match(match_expr) => Debug::fmt(match_expr, f),
+            Self::If(if_expr) => Debug::fmt(if_expr, f),
             Self::Literal(literal) => Debug::fmt(literal, f),
             Self::Var(ident) => f.write_str(&format!("Var({:?})", ident)),
             Self::App(app) => Debug::fmt(app, f),
+            Self::Binary(binary) => Debug::fmt(binary, f),
+            Self::Unary(unary) => Debug::fmt(unary, f),
+            Self::Ascribed(ascription) => Debug::fmt(ascription, f),
+            Self::Lambda(lambda) => Debug::fmt(lambda, f),
             Self::Paren(expr) => f.debug_tuple("Paren").field(expr).finish(),
+            Self::Tuple(elems) => f.debug_tuple("Tuple").field(elems).finish(),
+            Self::List(elems) => f.debug_tuple("List").field(elems).finish(),
+            Self::Record(record) => Debug::fmt(record, f),
+            Self::Field(access) => Debug::fmt(access, f),
+            Self::Error => f.write_str("Error"),
         }
     }
 }
@@ -63,10 +201,47 @@ pub struct Let {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub enum Pattern {
+pub struct Match {
+    pub scrutinee: Expr,
+    pub arms: Vec<MatchArm>,
+}
+
+/// `if cond then then_branch else else_branch`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct If {
+    pub cond: Expr,
+    pub then_branch: Expr,
+    pub else_branch: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: Expr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Pattern {
+    pub kind: PatternKind,
+    pub span: Span,
+}
+
+impl Pattern {
+    pub const fn new(kind: PatternKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PatternKind {
     Literal(Literal),
     Path(Path),
     Ident(Ident),
+    Tuple(Vec<Pattern>),
+    /// `Variant pattern ...`, where a lone capitalised name is a constructor
+    /// without fields rather than a binding
+    Constructor(Ident, Vec<Pattern>),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -80,16 +255,93 @@ pub enum Literal {
 pub struct App {
     pub func: Expr,
     pub args: Vec<Expr>,
+    pub style: AppStyle,
+}
+
+/// How an application was written, which only matters when formatting it
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum AppStyle {
+    /// `func <| args;`
+    LeftPipe,
+    /// `func args`
+    Juxtaposed,
+    /// `arg |> func args`, the piped argument comes after all of the others
+    Pipeline,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Binary {
+    pub lhs: Expr,
+    pub op: BinaryOp,
+    pub rhs: Expr,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum BinaryOp {
+    Eq,
+    NotEq,
+    Less,
+    LessEq,
+    Greater,
+    GreaterEq,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Unary {
+    pub op: UnaryOp,
+    pub expr: Expr,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum UnaryOp {
+    Not,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Ascription {
+    pub expr: Expr,
+    pub ty: Type,
+}
+
+/// `\params -> body`, an anonymous function that can use any variable in scope where it's written
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Lambda {
+    pub params: Vec<(Pattern, Type)>,
+    pub body: Expr,
+}
+
+/// `Name { field: value, ... }`, a value of a record type
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Record {
+    pub name: Ident,
+    pub fields: Vec<FieldInit>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FieldInit {
+    pub name: Ident,
+    pub value: Expr,
+    pub span: Span,
+}
+
+/// `record.field`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FieldAccess {
+    pub record: Expr,
+    pub field: Ident,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Path {
     pub segments: Vec<Ident>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Usage {
+    pub docs: Vec<String>,
     pub path: Path,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -104,7 +356,7 @@ pub enum PathOrIdent {
 }
 
 impl Path {
-    pub const fn new(segments: Vec<Ident>) -> Self {
-        Self { segments }
+    pub const fn new(segments: Vec<Ident>, span: Span) -> Self {
+        Self { segments, span }
     }
 }
@@ -0,0 +1,455 @@
+//! Rebuilding the AST by value
+//!
+//! Every `fold_*` method defaults to its matching `walk_*` function, which folds each
+//! of the node's children and puts the node back together around them. Overriding a
+//! method lets a pass replace a node with anything of the same type, such as when
+//! desugaring one expression into another
+
+use super::{
+    App, Ascription, Binary, Ensure, Expr, ExprKind, FieldAccess, FieldDef, FieldInit, FuncDef,
+    Ident, If, Item, Lambda, Let, Literal, Match, MatchArm, Module, Path, Pattern, PatternKind,
+    Record, Refinement, Type, TypeDef, TypeDefKind, TypeKind, Unary, Usage, VariantDef,
+};
+
+pub trait Fold: Sized {
+    fn fold_item(&mut self, item: Item) -> Item {
+        walk_item(self, item)
+    }
+
+    fn fold_func_def(&mut self, func: FuncDef) -> FuncDef {
+        walk_func_def(self, func)
+    }
+
+    fn fold_module(&mut self, module: Module) -> Module {
+        walk_module(self, module)
+    }
+
+    fn fold_usage(&mut self, usage: Usage) -> Usage {
+        walk_usage(self, usage)
+    }
+
+    fn fold_type_def(&mut self, def: TypeDef) -> TypeDef {
+        walk_type_def(self, def)
+    }
+
+    fn fold_type(&mut self, ty: Type) -> Type {
+        walk_type(self, ty)
+    }
+
+    fn fold_refinement(&mut self, refinement: Refinement) -> Refinement {
+        walk_refinement(self, refinement)
+    }
+
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        walk_expr(self, expr)
+    }
+
+    fn fold_let(&mut self, binding: Let) -> Let {
+        walk_let(self, binding)
+    }
+
+    fn fold_ensure(&mut self, ensure: Ensure) -> Ensure {
+        walk_ensure(self, ensure)
+    }
+
+    fn fold_match(&mut self, match_expr: Match) -> Match {
+        walk_match(self, match_expr)
+    }
+
+    fn fold_match_arm(&mut self, arm: MatchArm) -> MatchArm {
+        walk_match_arm(self, arm)
+    }
+
+    fn fold_if(&mut self, if_expr: If) -> If {
+        walk_if(self, if_expr)
+    }
+
+    fn fold_app(&mut self, app: App) -> App {
+        walk_app(self, app)
+    }
+
+    fn fold_binary(&mut self, binary: Binary) -> Binary {
+        walk_binary(self, binary)
+    }
+
+    fn fold_unary(&mut self, unary: Unary) -> Unary {
+        walk_unary(self, unary)
+    }
+
+    fn fold_ascription(&mut self, ascription: Ascription) -> Ascription {
+        walk_ascription(self, ascription)
+    }
+
+    fn fold_lambda(&mut self, lambda: Lambda) -> Lambda {
+        walk_lambda(self, lambda)
+    }
+
+    fn fold_record(&mut self, record: Record) -> Record {
+        walk_record(self, record)
+    }
+
+    fn fold_field_access(&mut self, access: FieldAccess) -> FieldAccess {
+        walk_field_access(self, access)
+    }
+
+    fn fold_pattern(&mut self, pattern: Pattern) -> Pattern {
+        walk_pattern(self, pattern)
+    }
+
+    fn fold_literal(&mut self, literal: Literal) -> Literal {
+        literal
+    }
+
+    fn fold_path(&mut self, path: Path) -> Path {
+        walk_path(self, path)
+    }
+
+    fn fold_ident(&mut self, ident: Ident) -> Ident {
+        ident
+    }
+}
+
+pub fn walk_item<F: Fold>(folder: &mut F, item: Item) -> Item {
+    match item {
+        Item::Func(func) => Item::Func(folder.fold_func_def(func)),
+        Item::Module(module) => Item::Module(folder.fold_module(module)),
+        Item::Usage(usage) => Item::Usage(folder.fold_usage(usage)),
+        Item::Type(def) => Item::Type(folder.fold_type_def(def)),
+        Item::Error(span) => Item::Error(span),
+    }
+}
+
+pub fn walk_func_def<F: Fold>(folder: &mut F, func: FuncDef) -> FuncDef {
+    FuncDef {
+        docs: func.docs,
+        name: folder.fold_ident(func.name),
+        params: func
+            .params
+            .into_iter()
+            .map(|(pattern, ty)| (folder.fold_pattern(pattern), folder.fold_type(ty)))
+            .collect(),
+        ret: func.ret.map(|ret| folder.fold_type(ret)),
+        body: func
+            .body
+            .into_iter()
+            .map(|expr| folder.fold_expr(expr))
+            .collect(),
+        span: func.span,
+    }
+}
+
+pub fn walk_module<F: Fold>(folder: &mut F, module: Module) -> Module {
+    Module {
+        docs: module.docs,
+        name: folder.fold_ident(module.name),
+        items: module
+            .items
+            .into_iter()
+            .map(|item| folder.fold_item(item))
+            .collect(),
+        span: module.span,
+    }
+}
+
+pub fn walk_usage<F: Fold>(folder: &mut F, usage: Usage) -> Usage {
+    Usage {
+        docs: usage.docs,
+        path: folder.fold_path(usage.path),
+        span: usage.span,
+    }
+}
+
+pub fn walk_type_def<F: Fold>(folder: &mut F, def: TypeDef) -> TypeDef {
+    let name = folder.fold_ident(def.name);
+    let kind = match def.kind {
+        TypeDefKind::Record(fields) => TypeDefKind::Record(
+            fields
+                .into_iter()
+                .map(|field| FieldDef {
+                    name: folder.fold_ident(field.name),
+                    ty: folder.fold_type(field.ty),
+                    span: field.span,
+                })
+                .collect(),
+        ),
+        TypeDefKind::Variants(variants) => TypeDefKind::Variants(
+            variants
+                .into_iter()
+                .map(|variant| VariantDef {
+                    name: folder.fold_ident(variant.name),
+                    fields: variant
+                        .fields
+                        .into_iter()
+                        .map(|field| folder.fold_type(field))
+                        .collect(),
+                    span: variant.span,
+                })
+                .collect(),
+        ),
+    };
+
+    TypeDef {
+        docs: def.docs,
+        name,
+        kind,
+        span: def.span,
+    }
+}
+
+pub fn walk_type<F: Fold>(folder: &mut F, ty: Type) -> Type {
+    let kind = match ty.kind {
+        TypeKind::Path(path) => TypeKind::Path(folder.fold_path(path)),
+        TypeKind::Tuple(elems) => TypeKind::Tuple(
+            elems
+                .into_iter()
+                .map(|elem| folder.fold_type(elem))
+                .collect(),
+        ),
+        TypeKind::List(elem) => TypeKind::List(Box::new(folder.fold_type(*elem))),
+        TypeKind::Func(args, ret) => TypeKind::Func(
+            args.into_iter().map(|arg| folder.fold_type(arg)).collect(),
+            Box::new(folder.fold_type(*ret)),
+        ),
+        TypeKind::Generic(name) => TypeKind::Generic(folder.fold_ident(name)),
+        TypeKind::Refined(refinement) => {
+            TypeKind::Refined(Box::new(folder.fold_refinement(*refinement)))
+        }
+        kind @ TypeKind::Bool | kind @ TypeKind::Int | kind @ TypeKind::String => kind,
+    };
+
+    Type::new(kind, ty.span)
+}
+
+pub fn walk_refinement<F: Fold>(folder: &mut F, refinement: Refinement) -> Refinement {
+    Refinement {
+        binder: folder.fold_ident(refinement.binder),
+        ty: folder.fold_type(refinement.ty),
+        predicate: folder.fold_expr(refinement.predicate),
+    }
+}
+
+pub fn walk_expr<F: Fold>(folder: &mut F, expr: Expr) -> Expr {
+    let kind = match expr.kind {
+        ExprKind::Let(binding) => ExprKind::Let(Box::new(folder.fold_let(*binding))),
+        ExprKind::Ensure(ensure) => ExprKind::Ensure(Box::new(folder.fold_ensure(*ensure))),
+        ExprKind::Match(match_expr) => ExprKind::Match(Box::new(folder.fold_match(*match_expr))),
+        ExprKind::If(if_expr) => ExprKind::If(Box::new(folder.fold_if(*if_expr))),
+        ExprKind::Literal(literal) => ExprKind::Literal(folder.fold_literal(literal)),
+        ExprKind::Var(var) => ExprKind::Var(folder.fold_ident(var)),
+        ExprKind::App(app) => ExprKind::App(Box::new(folder.fold_app(*app))),
+        ExprKind::Binary(binary) => ExprKind::Binary(Box::new(folder.fold_binary(*binary))),
+        ExprKind::Unary(unary) => ExprKind::Unary(Box::new(folder.fold_unary(*unary))),
+        ExprKind::Ascribed(ascription) => {
+            ExprKind::Ascribed(Box::new(folder.fold_ascription(*ascription)))
+        }
+        ExprKind::Lambda(lambda) => ExprKind::Lambda(Box::new(folder.fold_lambda(*lambda))),
+        ExprKind::Paren(inner) => ExprKind::Paren(Box::new(folder.fold_expr(*inner))),
+        ExprKind::Tuple(elems) => ExprKind::Tuple(
+            elems
+                .into_iter()
+                .map(|elem| folder.fold_expr(elem))
+                .collect(),
+        ),
+        ExprKind::List(elems) => ExprKind::List(
+            elems
+                .into_iter()
+                .map(|elem| folder.fold_expr(elem))
+                .collect(),
+        ),
+        ExprKind::Record(record) => ExprKind::Record(Box::new(folder.fold_record(*record))),
+        ExprKind::Field(access) => ExprKind::Field(Box::new(folder.fold_field_access(*access))),
+        ExprKind::Error => ExprKind::Error,
+    };
+
+    Expr::new(kind, expr.span)
+}
+
+pub fn walk_let<F: Fold>(folder: &mut F, binding: Let) -> Let {
+    Let {
+        binding: folder.fold_pattern(binding.binding),
+        value: folder.fold_expr(binding.value),
+    }
+}
+
+pub fn walk_ensure<F: Fold>(folder: &mut F, ensure: Ensure) -> Ensure {
+    Ensure {
+        contract: folder.fold_expr(ensure.contract),
+    }
+}
+
+pub fn walk_match<F: Fold>(folder: &mut F, match_expr: Match) -> Match {
+    Match {
+        scrutinee: folder.fold_expr(match_expr.scrutinee),
+        arms: match_expr
+            .arms
+            .into_iter()
+            .map(|arm| folder.fold_match_arm(arm))
+            .collect(),
+    }
+}
+
+pub fn walk_match_arm<F: Fold>(folder: &mut F, arm: MatchArm) -> MatchArm {
+    MatchArm {
+        pattern: folder.fold_pattern(arm.pattern),
+        body: folder.fold_expr(arm.body),
+        span: arm.span,
+    }
+}
+
+pub fn walk_if<F: Fold>(folder: &mut F, if_expr: If) -> If {
+    If {
+        cond: folder.fold_expr(if_expr.cond),
+        then_branch: folder.fold_expr(if_expr.then_branch),
+        else_branch: folder.fold_expr(if_expr.else_branch),
+    }
+}
+
+pub fn walk_app<F: Fold>(folder: &mut F, app: App) -> App {
+    App {
+        func: folder.fold_expr(app.func),
+        args: app
+            .args
+            .into_iter()
+            .map(|arg| folder.fold_expr(arg))
+            .collect(),
+        style: app.style,
+    }
+}
+
+pub fn walk_binary<F: Fold>(folder: &mut F, binary: Binary) -> Binary {
+    Binary {
+        lhs: folder.fold_expr(binary.lhs),
+        op: binary.op,
+        rhs: folder.fold_expr(binary.rhs),
+    }
+}
+
+pub fn walk_unary<F: Fold>(folder: &mut F, unary: Unary) -> Unary {
+    Unary {
+        op: unary.op,
+        expr: folder.fold_expr(unary.expr),
+    }
+}
+
+pub fn walk_ascription<F: Fold>(folder: &mut F, ascription: Ascription) -> Ascription {
+    Ascription {
+        expr: folder.fold_expr(ascription.expr),
+        ty: folder.fold_type(ascription.ty),
+    }
+}
+
+pub fn walk_lambda<F: Fold>(folder: &mut F, lambda: Lambda) -> Lambda {
+    Lambda {
+        params: lambda
+            .params
+            .into_iter()
+            .map(|(pattern, ty)| (folder.fold_pattern(pattern), folder.fold_type(ty)))
+            .collect(),
+        body: folder.fold_expr(lambda.body),
+    }
+}
+
+pub fn walk_record<F: Fold>(folder: &mut F, record: Record) -> Record {
+    Record {
+        name: folder.fold_ident(record.name),
+        fields: record
+            .fields
+            .into_iter()
+            .map(|field| FieldInit {
+                name: folder.fold_ident(field.name),
+                value: folder.fold_expr(field.value),
+                span: field.span,
+            })
+            .collect(),
+    }
+}
+
+pub fn walk_field_access<F: Fold>(folder: &mut F, access: FieldAccess) -> FieldAccess {
+    FieldAccess {
+        record: folder.fold_expr(access.record),
+        field: folder.fold_ident(access.field),
+    }
+}
+
+pub fn walk_pattern<F: Fold>(folder: &mut F, pattern: Pattern) -> Pattern {
+    let kind = match pattern.kind {
+        PatternKind::Literal(literal) => PatternKind::Literal(folder.fold_literal(literal)),
+        PatternKind::Path(path) => PatternKind::Path(folder.fold_path(path)),
+        PatternKind::Ident(name) => PatternKind::Ident(folder.fold_ident(name)),
+        PatternKind::Tuple(elems) => PatternKind::Tuple(
+            elems
+                .into_iter()
+                .map(|elem| folder.fold_pattern(elem))
+                .collect(),
+        ),
+        PatternKind::Constructor(name, args) => PatternKind::Constructor(
+            folder.fold_ident(name),
+            args.into_iter()
+                .map(|arg| folder.fold_pattern(arg))
+                .collect(),
+        ),
+    };
+
+    Pattern::new(kind, pattern.span)
+}
+
+pub fn walk_path<F: Fold>(folder: &mut F, path: Path) -> Path {
+    Path {
+        segments: path
+            .segments
+            .into_iter()
+            .map(|segment| folder.fold_ident(segment))
+            .collect(),
+        span: path.span,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax;
+
+    /// A file that uses every kind of node, with `var` and `bound` wherever an identifier can go
+    fn source(var: &str, bound: &str) -> String {
+        format!(
+            "type T = | A {var} | B\n\
+             type R = {{ {var}: '{var} }}\n\
+             module m =\n\
+             \x20   use n.{var}\n\
+             end\n\
+             fn f {var}: {{ v: Int => v > {bound} }} y: R -> Int =\n\
+             \x20   let ({var}, b) := ({var}, y);\n\
+             \x20   ensure not {bound}\n\
+             \x20   match {var} with\n\
+             \x20   | A {var} -> (if {var} then \\{var}: Int -> {var}\n\
+             \x20       else [d, R {{ {var}: {bound} }}.{var}])\n\
+             \x20   | _ -> ({var} <| i {bound};): Int\n",
+            var = var,
+            bound = bound,
+        )
+    }
+
+    #[test]
+    fn rebuilds_every_node_around_its_folded_children() {
+        struct Rename;
+
+        impl Fold for Rename {
+            fn fold_ident(&mut self, ident: Ident) -> Ident {
+                match ident.as_str() {
+                    "x" => Ident::new("k"),
+                    "lo" => Ident::new("hi"),
+                    _ => ident,
+                }
+            }
+        }
+
+        // The new names are the same length as the old ones so that every span still lines up
+        let items = syntax::lower(&syntax::parse(&source("x", "lo")));
+        let folded: Vec<_> = items
+            .into_iter()
+            .map(|item| Rename.fold_item(item))
+            .collect();
+
+        assert_eq!(folded, syntax::lower(&syntax::parse(&source("k", "hi"))));
+    }
+}
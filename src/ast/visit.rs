@@ -0,0 +1,376 @@
+//! Read-only traversal of the AST
+//!
+//! Every `visit_*` method defaults to its matching `walk_*` function, which visits
+//! each of the node's children in source order. Overriding a method replaces the
+//! traversal below that node, call the `walk_*` function from it to keep going
+
+use super::{
+    App, Ascription, Binary, Ensure, Expr, ExprKind, FieldAccess, FieldDef, FieldInit, FuncDef,
+    Ident, If, Item, Lambda, Let, Literal, Match, MatchArm, Module, Path, Pattern, PatternKind,
+    Record, Refinement, Type, TypeDef, TypeDefKind, TypeKind, Unary, Usage, VariantDef,
+};
+
+pub trait Visitor<'ast>: Sized {
+    fn visit_item(&mut self, item: &'ast Item) {
+        walk_item(self, item);
+    }
+
+    fn visit_func_def(&mut self, func: &'ast FuncDef) {
+        walk_func_def(self, func);
+    }
+
+    fn visit_module(&mut self, module: &'ast Module) {
+        walk_module(self, module);
+    }
+
+    fn visit_usage(&mut self, usage: &'ast Usage) {
+        walk_usage(self, usage);
+    }
+
+    fn visit_type_def(&mut self, def: &'ast TypeDef) {
+        walk_type_def(self, def);
+    }
+
+    fn visit_type(&mut self, ty: &'ast Type) {
+        walk_type(self, ty);
+    }
+
+    fn visit_refinement(&mut self, refinement: &'ast Refinement) {
+        walk_refinement(self, refinement);
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        walk_expr(self, expr);
+    }
+
+    fn visit_let(&mut self, binding: &'ast Let) {
+        walk_let(self, binding);
+    }
+
+    fn visit_ensure(&mut self, ensure: &'ast Ensure) {
+        walk_ensure(self, ensure);
+    }
+
+    fn visit_match(&mut self, match_expr: &'ast Match) {
+        walk_match(self, match_expr);
+    }
+
+    fn visit_match_arm(&mut self, arm: &'ast MatchArm) {
+        walk_match_arm(self, arm);
+    }
+
+    fn visit_if(&mut self, if_expr: &'ast If) {
+        walk_if(self, if_expr);
+    }
+
+    fn visit_app(&mut self, app: &'ast App) {
+        walk_app(self, app);
+    }
+
+    fn visit_binary(&mut self, binary: &'ast Binary) {
+        walk_binary(self, binary);
+    }
+
+    fn visit_unary(&mut self, unary: &'ast Unary) {
+        walk_unary(self, unary);
+    }
+
+    fn visit_ascription(&mut self, ascription: &'ast Ascription) {
+        walk_ascription(self, ascription);
+    }
+
+    fn visit_lambda(&mut self, lambda: &'ast Lambda) {
+        walk_lambda(self, lambda);
+    }
+
+    fn visit_record(&mut self, record: &'ast Record) {
+        walk_record(self, record);
+    }
+
+    fn visit_field_access(&mut self, access: &'ast FieldAccess) {
+        walk_field_access(self, access);
+    }
+
+    fn visit_pattern(&mut self, pattern: &'ast Pattern) {
+        walk_pattern(self, pattern);
+    }
+
+    fn visit_literal(&mut self, _literal: &'ast Literal) {}
+
+    fn visit_path(&mut self, path: &'ast Path) {
+        walk_path(self, path);
+    }
+
+    fn visit_ident(&mut self, _ident: &'ast Ident) {}
+}
+
+pub fn walk_item<'ast, V: Visitor<'ast>>(visitor: &mut V, item: &'ast Item) {
+    match item {
+        Item::Func(func) => visitor.visit_func_def(func),
+        Item::Module(module) => visitor.visit_module(module),
+        Item::Usage(usage) => visitor.visit_usage(usage),
+        Item::Type(def) => visitor.visit_type_def(def),
+        Item::Error(_) => {}
+    }
+}
+
+pub fn walk_func_def<'ast, V: Visitor<'ast>>(visitor: &mut V, func: &'ast FuncDef) {
+    visitor.visit_ident(&func.name);
+    for (pattern, ty) in func.params.iter() {
+        visitor.visit_pattern(pattern);
+        visitor.visit_type(ty);
+    }
+    if let Some(ret) = func.ret.as_ref() {
+        visitor.visit_type(ret);
+    }
+    for expr in func.body.iter() {
+        visitor.visit_expr(expr);
+    }
+}
+
+pub fn walk_module<'ast, V: Visitor<'ast>>(visitor: &mut V, module: &'ast Module) {
+    visitor.visit_ident(&module.name);
+    for item in module.items.iter() {
+        visitor.visit_item(item);
+    }
+}
+
+pub fn walk_usage<'ast, V: Visitor<'ast>>(visitor: &mut V, usage: &'ast Usage) {
+    visitor.visit_path(&usage.path);
+}
+
+pub fn walk_type_def<'ast, V: Visitor<'ast>>(visitor: &mut V, def: &'ast TypeDef) {
+    visitor.visit_ident(&def.name);
+    match &def.kind {
+        TypeDefKind::Record(fields) => {
+            for FieldDef { name, ty, .. } in fields {
+                visitor.visit_ident(name);
+                visitor.visit_type(ty);
+            }
+        }
+        TypeDefKind::Variants(variants) => {
+            for VariantDef { name, fields, .. } in variants {
+                visitor.visit_ident(name);
+                for field in fields {
+                    visitor.visit_type(field);
+                }
+            }
+        }
+    }
+}
+
+pub fn walk_type<'ast, V: Visitor<'ast>>(visitor: &mut V, ty: &'ast Type) {
+    match &ty.kind {
+        TypeKind::Path(path) => visitor.visit_path(path),
+        TypeKind::Tuple(elems) => {
+            for elem in elems {
+                visitor.visit_type(elem);
+            }
+        }
+        TypeKind::List(elem) => visitor.visit_type(elem),
+        TypeKind::Func(args, ret) => {
+            for arg in args {
+                visitor.visit_type(arg);
+            }
+            visitor.visit_type(ret);
+        }
+        TypeKind::Generic(name) => visitor.visit_ident(name),
+        TypeKind::Refined(refinement) => visitor.visit_refinement(refinement),
+        TypeKind::Bool | TypeKind::Int | TypeKind::String => {}
+    }
+}
+
+pub fn walk_refinement<'ast, V: Visitor<'ast>>(visitor: &mut V, refinement: &'ast Refinement) {
+    visitor.visit_ident(&refinement.binder);
+    visitor.visit_type(&refinement.ty);
+    visitor.visit_expr(&refinement.predicate);
+}
+
+pub fn walk_expr<'ast, V: Visitor<'ast>>(visitor: &mut V, expr: &'ast Expr) {
+    match &expr.kind {
+        ExprKind::Let(binding) => visitor.visit_let(binding),
+        ExprKind::Ensure(ensure) => visitor.visit_ensure(ensure),
+        ExprKind::Match(match_expr) => visitor.visit_match(match_expr),
+        ExprKind::If(if_expr) => visitor.visit_if(if_expr),
+        ExprKind::Literal(literal) => visitor.visit_literal(literal),
+        ExprKind::Var(var) => visitor.visit_ident(var),
+        ExprKind::App(app) => visitor.visit_app(app),
+        ExprKind::Binary(binary) => visitor.visit_binary(binary),
+        ExprKind::Unary(unary) => visitor.visit_unary(unary),
+        ExprKind::Ascribed(ascription) => visitor.visit_ascription(ascription),
+        ExprKind::Lambda(lambda) => visitor.visit_lambda(lambda),
+        ExprKind::Paren(inner) => visitor.visit_expr(inner),
+        ExprKind::Tuple(elems) | ExprKind::List(elems) => {
+            for elem in elems {
+                visitor.visit_expr(elem);
+            }
+        }
+        ExprKind::Record(record) => visitor.visit_record(record),
+        ExprKind::Field(access) => visitor.visit_field_access(access),
+        ExprKind::Error => {}
+    }
+}
+
+pub fn walk_let<'ast, V: Visitor<'ast>>(visitor: &mut V, binding: &'ast Let) {
+    visitor.visit_pattern(&binding.binding);
+    visitor.visit_expr(&binding.value);
+}
+
+pub fn walk_ensure<'ast, V: Visitor<'ast>>(visitor: &mut V, ensure: &'ast Ensure) {
+    visitor.visit_expr(&ensure.contract);
+}
+
+pub fn walk_match<'ast, V: Visitor<'ast>>(visitor: &mut V, match_expr: &'ast Match) {
+    visitor.visit_expr(&match_expr.scrutinee);
+    for arm in match_expr.arms.iter() {
+        visitor.visit_match_arm(arm);
+    }
+}
+
+pub fn walk_match_arm<'ast, V: Visitor<'ast>>(visitor: &mut V, arm: &'ast MatchArm) {
+    visitor.visit_pattern(&arm.pattern);
+    visitor.visit_expr(&arm.body);
+}
+
+pub fn walk_if<'ast, V: Visitor<'ast>>(visitor: &mut V, if_expr: &'ast If) {
+    visitor.visit_expr(&if_expr.cond);
+    visitor.visit_expr(&if_expr.then_branch);
+    visitor.visit_expr(&if_expr.else_branch);
+}
+
+pub fn walk_app<'ast, V: Visitor<'ast>>(visitor: &mut V, app: &'ast App) {
+    visitor.visit_expr(&app.func);
+    for arg in app.args.iter() {
+        visitor.visit_expr(arg);
+    }
+}
+
+pub fn walk_binary<'ast, V: Visitor<'ast>>(visitor: &mut V, binary: &'ast Binary) {
+    visitor.visit_expr(&binary.lhs);
+    visitor.visit_expr(&binary.rhs);
+}
+
+pub fn walk_unary<'ast, V: Visitor<'ast>>(visitor: &mut V, unary: &'ast Unary) {
+    visitor.visit_expr(&unary.expr);
+}
+
+pub fn walk_ascription<'ast, V: Visitor<'ast>>(visitor: &mut V, ascription: &'ast Ascription) {
+    visitor.visit_expr(&ascription.expr);
+    visitor.visit_type(&ascription.ty);
+}
+
+pub fn walk_lambda<'ast, V: Visitor<'ast>>(visitor: &mut V, lambda: &'ast Lambda) {
+    for (pattern, ty) in lambda.params.iter() {
+        visitor.visit_pattern(pattern);
+        visitor.visit_type(ty);
+    }
+    visitor.visit_expr(&lambda.body);
+}
+
+pub fn walk_record<'ast, V: Visitor<'ast>>(visitor: &mut V, record: &'ast Record) {
+    visitor.visit_ident(&record.name);
+    for FieldInit { name, value, .. } in record.fields.iter() {
+        visitor.visit_ident(name);
+        visitor.visit_expr(value);
+    }
+}
+
+pub fn walk_field_access<'ast, V: Visitor<'ast>>(visitor: &mut V, access: &'ast FieldAccess) {
+    visitor.visit_expr(&access.record);
+    visitor.visit_ident(&access.field);
+}
+
+pub fn walk_pattern<'ast, V: Visitor<'ast>>(visitor: &mut V, pattern: &'ast Pattern) {
+    match &pattern.kind {
+        PatternKind::Literal(literal) => visitor.visit_literal(literal),
+        PatternKind::Path(path) => visitor.visit_path(path),
+        PatternKind::Ident(name) => visitor.visit_ident(name),
+        PatternKind::Tuple(elems) => {
+            for elem in elems {
+                visitor.visit_pattern(elem);
+            }
+        }
+        PatternKind::Constructor(name, args) => {
+            visitor.visit_ident(name);
+            for arg in args {
+                visitor.visit_pattern(arg);
+            }
+        }
+    }
+}
+
+pub fn walk_path<'ast, V: Visitor<'ast>>(visitor: &mut V, path: &'ast Path) {
+    for segment in path.segments.iter() {
+        visitor.visit_ident(segment);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax;
+
+    /// Collects every identifier in the order it's visited
+    #[derive(Default)]
+    struct Idents(Vec<&'static str>);
+
+    impl<'ast> Visitor<'ast> for Idents {
+        fn visit_ident(&mut self, ident: &'ast Ident) {
+            self.0.push(ident.as_str());
+        }
+    }
+
+    fn idents(source: &str) -> Vec<&'static str> {
+        let items = syntax::lower(&syntax::parse(source));
+
+        let mut idents = Idents::default();
+        for item in items.iter() {
+            idents.visit_item(item);
+        }
+
+        idents.0
+    }
+
+    #[test]
+    fn walks_every_node_in_source_order() {
+        assert_eq!(
+            idents(
+                "type T = | A Int | B\n\
+                type R = { f: 'a }\n\
+                module m =\n\
+                \x20   use n.g\n\
+                end\n\
+                fn f x: { v: Int => v > lo } y: R -> Int =\n\
+                \x20   let (a, b) := (x, y);\n\
+                \x20   ensure not c\n\
+                \x20   match a with\n\
+                \x20   | A z -> (if z then \\w: Int -> w else [d, R { f: e }.f])\n\
+                \x20   | _ -> (h <| i j;): Int\n",
+            ),
+            [
+                "T", "A", "B", "R", "f", "a", "m", "n", "g", "f", "x", "v", "v", "lo", "y", "R",
+                "a", "b", "x", "y", "c", "a", "A", "z", "z", "w", "w", "d", "R", "f", "e", "f",
+                "_", "h", "i", "j",
+            ],
+        );
+    }
+
+    #[test]
+    fn overriding_a_method_replaces_the_walk_below_it() {
+        struct SkipLambdas(Idents);
+
+        impl<'ast> Visitor<'ast> for SkipLambdas {
+            fn visit_ident(&mut self, ident: &'ast Ident) {
+                self.0.visit_ident(ident);
+            }
+
+            fn visit_lambda(&mut self, _lambda: &'ast Lambda) {}
+        }
+
+        let items = syntax::lower(&syntax::parse("fn f = g (\\x: Int -> y) z\n"));
+        let mut visitor = SkipLambdas(Idents::default());
+        visitor.visit_item(&items[0]);
+
+        assert_eq!((visitor.0).0, ["f", "g", "z"]);
+    }
+}
@@ -0,0 +1,348 @@
+//! Traversal of the AST that can modify it in place
+//!
+//! Works just like [`Visitor`](super::visit::Visitor) except that every node is
+//! handed out mutably
+
+use super::{
+    App, Ascription, Binary, Ensure, Expr, ExprKind, FieldAccess, FieldDef, FieldInit, FuncDef,
+    Ident, If, Item, Lambda, Let, Literal, Match, MatchArm, Module, Path, Pattern, PatternKind,
+    Record, Refinement, Type, TypeDef, TypeDefKind, TypeKind, Unary, Usage, VariantDef,
+};
+
+pub trait VisitorMut: Sized {
+    fn visit_item_mut(&mut self, item: &mut Item) {
+        walk_item(self, item);
+    }
+
+    fn visit_func_def_mut(&mut self, func: &mut FuncDef) {
+        walk_func_def(self, func);
+    }
+
+    fn visit_module_mut(&mut self, module: &mut Module) {
+        walk_module(self, module);
+    }
+
+    fn visit_usage_mut(&mut self, usage: &mut Usage) {
+        walk_usage(self, usage);
+    }
+
+    fn visit_type_def_mut(&mut self, def: &mut TypeDef) {
+        walk_type_def(self, def);
+    }
+
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        walk_type(self, ty);
+    }
+
+    fn visit_refinement_mut(&mut self, refinement: &mut Refinement) {
+        walk_refinement(self, refinement);
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        walk_expr(self, expr);
+    }
+
+    fn visit_let_mut(&mut self, binding: &mut Let) {
+        walk_let(self, binding);
+    }
+
+    fn visit_ensure_mut(&mut self, ensure: &mut Ensure) {
+        walk_ensure(self, ensure);
+    }
+
+    fn visit_match_mut(&mut self, match_expr: &mut Match) {
+        walk_match(self, match_expr);
+    }
+
+    fn visit_match_arm_mut(&mut self, arm: &mut MatchArm) {
+        walk_match_arm(self, arm);
+    }
+
+    fn visit_if_mut(&mut self, if_expr: &mut If) {
+        walk_if(self, if_expr);
+    }
+
+    fn visit_app_mut(&mut self, app: &mut App) {
+        walk_app(self, app);
+    }
+
+    fn visit_binary_mut(&mut self, binary: &mut Binary) {
+        walk_binary(self, binary);
+    }
+
+    fn visit_unary_mut(&mut self, unary: &mut Unary) {
+        walk_unary(self, unary);
+    }
+
+    fn visit_ascription_mut(&mut self, ascription: &mut Ascription) {
+        walk_ascription(self, ascription);
+    }
+
+    fn visit_lambda_mut(&mut self, lambda: &mut Lambda) {
+        walk_lambda(self, lambda);
+    }
+
+    fn visit_record_mut(&mut self, record: &mut Record) {
+        walk_record(self, record);
+    }
+
+    fn visit_field_access_mut(&mut self, access: &mut FieldAccess) {
+        walk_field_access(self, access);
+    }
+
+    fn visit_pattern_mut(&mut self, pattern: &mut Pattern) {
+        walk_pattern(self, pattern);
+    }
+
+    fn visit_literal_mut(&mut self, _literal: &mut Literal) {}
+
+    fn visit_path_mut(&mut self, path: &mut Path) {
+        walk_path(self, path);
+    }
+
+    fn visit_ident_mut(&mut self, _ident: &mut Ident) {}
+}
+
+pub fn walk_item<V: VisitorMut>(visitor: &mut V, item: &mut Item) {
+    match item {
+        Item::Func(func) => visitor.visit_func_def_mut(func),
+        Item::Module(module) => visitor.visit_module_mut(module),
+        Item::Usage(usage) => visitor.visit_usage_mut(usage),
+        Item::Type(def) => visitor.visit_type_def_mut(def),
+        Item::Error(_) => {}
+    }
+}
+
+pub fn walk_func_def<V: VisitorMut>(visitor: &mut V, func: &mut FuncDef) {
+    visitor.visit_ident_mut(&mut func.name);
+    for (pattern, ty) in func.params.iter_mut() {
+        visitor.visit_pattern_mut(pattern);
+        visitor.visit_type_mut(ty);
+    }
+    if let Some(ret) = func.ret.as_mut() {
+        visitor.visit_type_mut(ret);
+    }
+    for expr in func.body.iter_mut() {
+        visitor.visit_expr_mut(expr);
+    }
+}
+
+pub fn walk_module<V: VisitorMut>(visitor: &mut V, module: &mut Module) {
+    visitor.visit_ident_mut(&mut module.name);
+    for item in module.items.iter_mut() {
+        visitor.visit_item_mut(item);
+    }
+}
+
+pub fn walk_usage<V: VisitorMut>(visitor: &mut V, usage: &mut Usage) {
+    visitor.visit_path_mut(&mut usage.path);
+}
+
+pub fn walk_type_def<V: VisitorMut>(visitor: &mut V, def: &mut TypeDef) {
+    visitor.visit_ident_mut(&mut def.name);
+    match &mut def.kind {
+        TypeDefKind::Record(fields) => {
+            for FieldDef { name, ty, .. } in fields {
+                visitor.visit_ident_mut(name);
+                visitor.visit_type_mut(ty);
+            }
+        }
+        TypeDefKind::Variants(variants) => {
+            for VariantDef { name, fields, .. } in variants {
+                visitor.visit_ident_mut(name);
+                for field in fields {
+                    visitor.visit_type_mut(field);
+                }
+            }
+        }
+    }
+}
+
+pub fn walk_type<V: VisitorMut>(visitor: &mut V, ty: &mut Type) {
+    match &mut ty.kind {
+        TypeKind::Path(path) => visitor.visit_path_mut(path),
+        TypeKind::Tuple(elems) => {
+            for elem in elems {
+                visitor.visit_type_mut(elem);
+            }
+        }
+        TypeKind::List(elem) => visitor.visit_type_mut(elem),
+        TypeKind::Func(args, ret) => {
+            for arg in args {
+                visitor.visit_type_mut(arg);
+            }
+            visitor.visit_type_mut(ret);
+        }
+        TypeKind::Generic(name) => visitor.visit_ident_mut(name),
+        TypeKind::Refined(refinement) => visitor.visit_refinement_mut(refinement),
+        TypeKind::Bool | TypeKind::Int | TypeKind::String => {}
+    }
+}
+
+pub fn walk_refinement<V: VisitorMut>(visitor: &mut V, refinement: &mut Refinement) {
+    visitor.visit_ident_mut(&mut refinement.binder);
+    visitor.visit_type_mut(&mut refinement.ty);
+    visitor.visit_expr_mut(&mut refinement.predicate);
+}
+
+pub fn walk_expr<V: VisitorMut>(visitor: &mut V, expr: &mut Expr) {
+    match &mut expr.kind {
+        ExprKind::Let(binding) => visitor.visit_let_mut(binding),
+        ExprKind::Ensure(ensure) => visitor.visit_ensure_mut(ensure),
+        ExprKind::Match(match_expr) => visitor.visit_match_mut(match_expr),
+        ExprKind::If(if_expr) => visitor.visit_if_mut(if_expr),
+        ExprKind::Literal(literal) => visitor.visit_literal_mut(literal),
+        ExprKind::Var(var) => visitor.visit_ident_mut(var),
+        ExprKind::App(app) => visitor.visit_app_mut(app),
+        ExprKind::Binary(binary) => visitor.visit_binary_mut(binary),
+        ExprKind::Unary(unary) => visitor.visit_unary_mut(unary),
+        ExprKind::Ascribed(ascription) => visitor.visit_ascription_mut(ascription),
+        ExprKind::Lambda(lambda) => visitor.visit_lambda_mut(lambda),
+        ExprKind::Paren(inner) => visitor.visit_expr_mut(inner),
+        ExprKind::Tuple(elems) | ExprKind::List(elems) => {
+            for elem in elems {
+                visitor.visit_expr_mut(elem);
+            }
+        }
+        ExprKind::Record(record) => visitor.visit_record_mut(record),
+        ExprKind::Field(access) => visitor.visit_field_access_mut(access),
+        ExprKind::Error => {}
+    }
+}
+
+pub fn walk_let<V: VisitorMut>(visitor: &mut V, binding: &mut Let) {
+    visitor.visit_pattern_mut(&mut binding.binding);
+    visitor.visit_expr_mut(&mut binding.value);
+}
+
+pub fn walk_ensure<V: VisitorMut>(visitor: &mut V, ensure: &mut Ensure) {
+    visitor.visit_expr_mut(&mut ensure.contract);
+}
+
+pub fn walk_match<V: VisitorMut>(visitor: &mut V, match_expr: &mut Match) {
+    visitor.visit_expr_mut(&mut match_expr.scrutinee);
+    for arm in match_expr.arms.iter_mut() {
+        visitor.visit_match_arm_mut(arm);
+    }
+}
+
+pub fn walk_match_arm<V: VisitorMut>(visitor: &mut V, arm: &mut MatchArm) {
+    visitor.visit_pattern_mut(&mut arm.pattern);
+    visitor.visit_expr_mut(&mut arm.body);
+}
+
+pub fn walk_if<V: VisitorMut>(visitor: &mut V, if_expr: &mut If) {
+    visitor.visit_expr_mut(&mut if_expr.cond);
+    visitor.visit_expr_mut(&mut if_expr.then_branch);
+    visitor.visit_expr_mut(&mut if_expr.else_branch);
+}
+
+pub fn walk_app<V: VisitorMut>(visitor: &mut V, app: &mut App) {
+    visitor.visit_expr_mut(&mut app.func);
+    for arg in app.args.iter_mut() {
+        visitor.visit_expr_mut(arg);
+    }
+}
+
+pub fn walk_binary<V: VisitorMut>(visitor: &mut V, binary: &mut Binary) {
+    visitor.visit_expr_mut(&mut binary.lhs);
+    visitor.visit_expr_mut(&mut binary.rhs);
+}
+
+pub fn walk_unary<V: VisitorMut>(visitor: &mut V, unary: &mut Unary) {
+    visitor.visit_expr_mut(&mut unary.expr);
+}
+
+pub fn walk_ascription<V: VisitorMut>(visitor: &mut V, ascription: &mut Ascription) {
+    visitor.visit_expr_mut(&mut ascription.expr);
+    visitor.visit_type_mut(&mut ascription.ty);
+}
+
+pub fn walk_lambda<V: VisitorMut>(visitor: &mut V, lambda: &mut Lambda) {
+    for (pattern, ty) in lambda.params.iter_mut() {
+        visitor.visit_pattern_mut(pattern);
+        visitor.visit_type_mut(ty);
+    }
+    visitor.visit_expr_mut(&mut lambda.body);
+}
+
+pub fn walk_record<V: VisitorMut>(visitor: &mut V, record: &mut Record) {
+    visitor.visit_ident_mut(&mut record.name);
+    for FieldInit { name, value, .. } in record.fields.iter_mut() {
+        visitor.visit_ident_mut(name);
+        visitor.visit_expr_mut(value);
+    }
+}
+
+pub fn walk_field_access<V: VisitorMut>(visitor: &mut V, access: &mut FieldAccess) {
+    visitor.visit_expr_mut(&mut access.record);
+    visitor.visit_ident_mut(&mut access.field);
+}
+
+pub fn walk_pattern<V: VisitorMut>(visitor: &mut V, pattern: &mut Pattern) {
+    match &mut pattern.kind {
+        PatternKind::Literal(literal) => visitor.visit_literal_mut(literal),
+        PatternKind::Path(path) => visitor.visit_path_mut(path),
+        PatternKind::Ident(name) => visitor.visit_ident_mut(name),
+        PatternKind::Tuple(elems) => {
+            for elem in elems {
+                visitor.visit_pattern_mut(elem);
+            }
+        }
+        PatternKind::Constructor(name, args) => {
+            visitor.visit_ident_mut(name);
+            for arg in args {
+                visitor.visit_pattern_mut(arg);
+            }
+        }
+    }
+}
+
+pub fn walk_path<V: VisitorMut>(visitor: &mut V, path: &mut Path) {
+    for segment in path.segments.iter_mut() {
+        visitor.visit_ident_mut(segment);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax;
+
+    /// A file with integers `small` and `large` in all of the places a literal can go
+    fn source(small: i64, large: i64) -> String {
+        format!(
+            "type R = {{ f: Int }}\n\
+             fn f x: {{ v: Int => v > {small} }} -> Int =\n\
+             \x20   let (a, b) := ({small}, x);\n\
+             \x20   ensure not ({small} == {large})\n\
+             \x20   match a with\n\
+             \x20   | {small} -> (if True then \\w: Int -> {large}\n\
+             \x20       else [{small}, R {{ f: {large} }}.f])\n\
+             \x20   | (_, {large}) -> (h <| {small} {large};): Int\n",
+            small = small,
+            large = large,
+        )
+    }
+
+    #[test]
+    fn reaches_every_literal_in_place() {
+        struct Increment;
+
+        impl VisitorMut for Increment {
+            fn visit_literal_mut(&mut self, literal: &mut Literal) {
+                if let Literal::Int(int) = literal {
+                    *int += 1;
+                }
+            }
+        }
+
+        // Single digit integers stay the same length so that every span still lines up
+        let mut items = syntax::lower(&syntax::parse(&source(1, 2)));
+        for item in items.iter_mut() {
+            Increment.visit_item_mut(item);
+        }
+
+        assert_eq!(items, syntax::lower(&syntax::parse(&source(2, 3))));
+    }
+}
@@ -0,0 +1,868 @@
+//! Formatting source code into its canonical layout
+//!
+//! The AST doesn't keep comments around, so they're collected separately by the
+//! lexer and attached back to nodes by their spans. A comment belongs to the node
+//! it's on the same line as the end of, or otherwise to the node that follows it
+
+use crate::{
+    ast::{self, Span},
+    lexer::Comment,
+    pretty::Doc,
+};
+
+/// The line width used when none is given
+pub const DEFAULT_WIDTH: usize = 100;
+
+const INDENT: usize = 4;
+
+/// Formats a file's items, `comments` must be every comment within `source` in order
+pub fn format(source: &str, items: &[ast::Item], comments: &[Comment<'_>], width: usize) -> String {
+    let mut formatter = Formatter {
+        source,
+        comments,
+        next_comment: 0,
+        end: source.len(),
+    };
+
+    let doc = formatter.sequence(items, source.len(), ast::Item::span, true, Formatter::item);
+    let mut formatted = doc.render(width);
+
+    let len = formatted.trim_end().len();
+    formatted.truncate(len);
+    if !formatted.is_empty() {
+        formatted.push('\n');
+    }
+
+    formatted
+}
+
+struct Formatter<'a, 'c> {
+    source: &'a str,
+    comments: &'c [Comment<'a>],
+    next_comment: usize,
+    /// Where the statement or item being formatted ends, the comments after it are
+    /// left for it to claim rather than whatever node inside of it ends last
+    end: usize,
+}
+
+impl<'a> Formatter<'a, '_> {
+    /// Takes the next comment if it starts before `end`
+    fn comment_before(&mut self, end: usize) -> Option<Doc> {
+        let comment = self.comments.get(self.next_comment)?;
+        if comment.span.start >= end {
+            return None;
+        }
+
+        self.next_comment += 1;
+        Some(Doc::text(comment.text))
+    }
+
+    /// Takes the next comment if it's within or on the same line as the end of `span`
+    fn trailing_comment(&mut self, span: Span) -> Option<Doc> {
+        let comment = self.comments.get(self.next_comment)?;
+        let same_line = comment.span.start < span.end
+            || !self.source[span.end..comment.span.start].contains('\n');
+
+        if same_line {
+            self.next_comment += 1;
+            Some(Doc::text(comment.text))
+        } else {
+            None
+        }
+    }
+
+    /// Takes the next comment if it starts before `end` and isn't held back for the
+    /// enclosing statement or item
+    fn nested_comment(&mut self, end: usize) -> Option<Comment<'a>> {
+        let comment = *self.comments.get(self.next_comment)?;
+        if comment.span.start >= end.min(self.end) {
+            return None;
+        }
+
+        self.next_comment += 1;
+        Some(comment)
+    }
+
+    /// Formats a node along with the comments attached to it
+    fn node(&mut self, span: Span, format: impl FnOnce(&mut Self) -> Doc) -> Doc {
+        let leading = self.leading_comments(span.start);
+        let doc = format(self);
+        let trailing = self.trailing_comments(span.end);
+
+        Doc::concat(vec![leading, doc, trailing])
+    }
+
+    /// The comments before `start`, each on its own line unless it's a block comment
+    /// with more code after it on the same line
+    fn leading_comments(&mut self, start: usize) -> Doc {
+        let mut docs = Vec::new();
+        while let Some(comment) = self.nested_comment(start) {
+            let next_start = self
+                .comments
+                .get(self.next_comment)
+                .map_or(start, |next| next.span.start.min(start));
+            let same_line = !comment.text.starts_with("--")
+                && !self.source[comment.span.end..next_start].contains('\n');
+
+            docs.push(Doc::text(comment.text));
+            docs.push(if same_line {
+                Doc::text(" ")
+            } else {
+                Doc::HardLine
+            });
+        }
+
+        Doc::concat(docs)
+    }
+
+    /// The comments left within a node that ends at `end` along with the ones after it
+    /// on the same line past nothing but a separator. Line comments are held until the
+    /// end of the line they end up on so that they can't swallow any code
+    fn trailing_comments(&mut self, mut end: usize) -> Doc {
+        let mut docs = Vec::new();
+        while let Some(&comment) = self.comments.get(self.next_comment) {
+            let within = comment.span.start < end
+                || self.source[end..comment.span.start]
+                    .chars()
+                    .all(|chr| chr == ',' || (chr.is_whitespace() && chr != '\n'));
+            if !within || comment.span.start >= self.end {
+                break;
+            }
+            self.next_comment += 1;
+
+            if comment.text.starts_with("--") {
+                docs.push(Doc::line_suffix(format!(" {}", comment.text)));
+                break;
+            }
+
+            docs.extend(vec![Doc::text(" "), Doc::text(comment.text)]);
+            end = end.max(comment.span.end);
+        }
+
+        Doc::concat(docs)
+    }
+
+    /// Lays out items or statements one per line along with the comments around them,
+    /// `spaced` puts a blank line between every element, otherwise only the ones the
+    /// source had are kept
+    fn sequence<T>(
+        &mut self,
+        elems: &[T],
+        end: usize,
+        span: fn(&T) -> Span,
+        spaced: bool,
+        mut format: impl FnMut(&mut Self, &T) -> Doc,
+    ) -> Doc {
+        let mut docs = Vec::new();
+        let mut last_end = None;
+
+        for elem in elems {
+            let span = span(elem);
+
+            if let Some(last_end) = last_end {
+                let next_start = self
+                    .comments
+                    .get(self.next_comment)
+                    .map_or(span.start, |comment| comment.span.start.min(span.start));
+                let blank_line = self.source[last_end..next_start].matches('\n').count() > 1;
+
+                docs.push(Doc::HardLine);
+                if spaced || blank_line {
+                    docs.push(Doc::HardLine);
+                }
+            }
+
+            while let Some(comment) = self.comment_before(span.start) {
+                docs.extend(vec![comment, Doc::HardLine]);
+
+                // Keep comments that were separated from what follows them that way
+                let comment_end = self.comments[self.next_comment - 1].span.end;
+                let next_start = self
+                    .comments
+                    .get(self.next_comment)
+                    .map_or(span.start, |comment| comment.span.start.min(span.start));
+                if self.source[comment_end..next_start].matches('\n').count() > 1 {
+                    docs.push(Doc::HardLine);
+                }
+            }
+
+            let outer_end = std::mem::replace(&mut self.end, span.end);
+            docs.push(format(self, elem));
+            self.end = outer_end;
+
+            let mut trailing = 0;
+            while let Some(comment) = self.trailing_comment(span) {
+                docs.push(if trailing == 0 {
+                    Doc::text(" ")
+                } else {
+                    Doc::HardLine
+                });
+                docs.push(comment);
+                trailing += 1;
+            }
+
+            last_end = Some(span.end);
+        }
+
+        while let Some(comment) = self.comment_before(end) {
+            if !docs.is_empty() {
+                docs.push(Doc::HardLine);
+            }
+            docs.push(comment);
+        }
+
+        Doc::concat(docs)
+    }
+
+    fn item(&mut self, item: &ast::Item) -> Doc {
+        match item {
+            ast::Item::Func(func) => {
+                let mut signature = Vec::new();
+                for (pattern, ty) in func.params.iter() {
+                    signature.push(Doc::Line);
+                    signature.push(self.param(pattern, ty));
+                }
+
+                if let Some(ret) = func.ret.as_ref() {
+                    signature.push(Doc::Line);
+                    signature.push(Doc::concat(vec![Doc::text("-> "), self.ty(ret)]));
+                }
+
+                let header = Doc::group(Doc::concat(vec![
+                    Doc::text(format!("fn {}", func.name)),
+                    Doc::nest(INDENT, Doc::concat(signature)),
+                    Doc::text(" ="),
+                ]));
+                let body = self.sequence(
+                    &func.body,
+                    func.span.end,
+                    |expr| expr.span,
+                    false,
+                    Self::expr,
+                );
+
+                Doc::concat(vec![
+                    docs(&func.docs),
+                    header,
+                    Doc::nest(INDENT, Doc::concat(vec![Doc::HardLine, body])),
+                ])
+            }
+
+            ast::Item::Module(module) => {
+                // Comments before the closing `end` stay inside the module
+                let end = module.span.end - "end".len();
+                let items = self.sequence(&module.items, end, ast::Item::span, true, Self::item);
+
+                Doc::concat(vec![
+                    docs(&module.docs),
+                    Doc::text(format!("module {} =", module.name)),
+                    Doc::nest(INDENT, Doc::concat(vec![Doc::HardLine, items])),
+                    Doc::HardLine,
+                    Doc::text("end"),
+                ])
+            }
+
+            ast::Item::Usage(usage) => Doc::concat(vec![
+                docs(&usage.docs),
+                Doc::text(format!("use {}", path(&usage.path))),
+            ]),
+
+            ast::Item::Type(def) => {
+                let body = match &def.kind {
+                    ast::TypeDefKind::Record(fields) => {
+                        let fields = fields
+                            .iter()
+                            .map(|field| {
+                                self.node(field.span, |this| {
+                                    Doc::concat(vec![
+                                        Doc::text(format!("{}: ", field.name)),
+                                        this.ty(&field.ty),
+                                    ])
+                                })
+                            })
+                            .collect();
+                        Doc::concat(vec![Doc::text(" "), braces(fields)])
+                    }
+
+                    ast::TypeDefKind::Variants(variants) => {
+                        let mut docs = Vec::new();
+                        for variant in variants {
+                            docs.push(Doc::Line);
+                            docs.push(self.node(variant.span, |this| {
+                                let mut docs = vec![Doc::text(format!("| {}", variant.name))];
+                                for field in &variant.fields {
+                                    docs.extend(vec![Doc::text(" "), this.atomic_ty(field)]);
+                                }
+
+                                Doc::concat(docs)
+                            }));
+                        }
+
+                        Doc::group(Doc::nest(INDENT, Doc::concat(docs)))
+                    }
+                };
+
+                Doc::concat(vec![
+                    docs(&def.docs),
+                    Doc::text(format!("type {} =", def.name)),
+                    body,
+                ])
+            }
+
+            // Files with syntax errors are never formatted
+            ast::Item::Error(span) => Doc::text(self.text(*span)),
+        }
+    }
+
+    fn expr(&mut self, expr: &ast::Expr) -> Doc {
+        self.node(expr.span, |this| this.expr_kind(expr))
+    }
+
+    fn expr_kind(&mut self, expr: &ast::Expr) -> Doc {
+        match &expr.kind {
+            ast::ExprKind::Let(binding) => Doc::group(Doc::concat(vec![
+                Doc::text("let "),
+                self.pattern(&binding.binding),
+                Doc::text(" :="),
+                Doc::nest(
+                    INDENT,
+                    Doc::concat(vec![Doc::Line, self.expr(&binding.value)]),
+                ),
+                Doc::text(";"),
+            ])),
+
+            ast::ExprKind::Ensure(ensure) => {
+                Doc::concat(vec![Doc::text("ensure "), self.expr(&ensure.contract)])
+            }
+
+            ast::ExprKind::Match(match_expr) => {
+                let mut arms = Vec::new();
+                for arm in match_expr.arms.iter() {
+                    arms.push(Doc::HardLine);
+                    arms.push(self.node(arm.span, |this| {
+                        Doc::concat(vec![
+                            Doc::text("| "),
+                            this.pattern(&arm.pattern),
+                            Doc::text(" -> "),
+                            this.expr(&arm.body),
+                        ])
+                    }));
+                }
+
+                Doc::concat(vec![
+                    Doc::text("match "),
+                    self.expr(&match_expr.scrutinee),
+                    Doc::text(" with"),
+                    Doc::nest(INDENT, Doc::concat(arms)),
+                ])
+            }
+
+            ast::ExprKind::If(if_expr) => Doc::group(Doc::concat(vec![
+                Doc::text("if "),
+                self.expr(&if_expr.cond),
+                Doc::text(" then"),
+                Doc::nest(
+                    INDENT,
+                    Doc::concat(vec![Doc::Line, self.expr(&if_expr.then_branch)]),
+                ),
+                Doc::Line,
+                Doc::text("else"),
+                Doc::nest(
+                    INDENT,
+                    Doc::concat(vec![Doc::Line, self.expr(&if_expr.else_branch)]),
+                ),
+            ])),
+
+            ast::ExprKind::App(app) => match app.style {
+                ast::AppStyle::LeftPipe => Doc::group(Doc::concat(vec![
+                    self.expr(&app.func),
+                    Doc::text(" <|"),
+                    self.args(&app.args),
+                    Doc::text(";"),
+                ])),
+
+                ast::AppStyle::Juxtaposed => Doc::group(Doc::concat(vec![
+                    self.expr(&app.func),
+                    self.args(&app.args),
+                ])),
+
+                ast::AppStyle::Pipeline => {
+                    let (arg, args) = app.args.split_last().unwrap();
+                    let arg = self.expr(arg);
+                    let func = Doc::group(Doc::concat(vec![self.expr(&app.func), self.args(args)]));
+
+                    Doc::group(Doc::concat(vec![
+                        arg,
+                        Doc::nest(INDENT, Doc::concat(vec![Doc::Line, Doc::text("|> "), func])),
+                    ]))
+                }
+            },
+
+            ast::ExprKind::Binary(binary) => Doc::group(Doc::concat(vec![
+                self.expr(&binary.lhs),
+                Doc::text(format!(" {}", binary_op(binary.op))),
+                Doc::nest(INDENT, Doc::concat(vec![Doc::Line, self.expr(&binary.rhs)])),
+            ])),
+
+            ast::ExprKind::Unary(unary) => match unary.op {
+                ast::UnaryOp::Not => Doc::concat(vec![Doc::text("not "), self.expr(&unary.expr)]),
+            },
+
+            ast::ExprKind::Ascribed(ascription) => Doc::concat(vec![
+                self.expr(&ascription.expr),
+                Doc::text(": "),
+                self.ty(&ascription.ty),
+            ]),
+
+            ast::ExprKind::Lambda(lambda) => {
+                let mut params = Vec::new();
+                for (idx, (pattern, ty)) in lambda.params.iter().enumerate() {
+                    if idx != 0 {
+                        params.push(Doc::Line);
+                    }
+                    params.push(self.param(pattern, ty));
+                }
+
+                Doc::group(Doc::concat(vec![
+                    Doc::text("\\"),
+                    Doc::group(Doc::nest(INDENT, Doc::concat(params))),
+                    Doc::text(" ->"),
+                    Doc::nest(
+                        INDENT,
+                        Doc::concat(vec![Doc::Line, self.expr(&lambda.body)]),
+                    ),
+                ]))
+            }
+
+            ast::ExprKind::Paren(inner) => Doc::group(Doc::concat(vec![
+                Doc::text("("),
+                Doc::nest(INDENT, Doc::concat(vec![Doc::SoftLine, self.expr(inner)])),
+                Doc::SoftLine,
+                Doc::text(")"),
+            ])),
+
+            ast::ExprKind::Tuple(elems) => {
+                let elems = elems.iter().map(|elem| self.expr(elem)).collect();
+                tuple(elems)
+            }
+
+            ast::ExprKind::List(elems) => {
+                let elems = elems.iter().map(|elem| self.expr(elem)).collect();
+                list(elems)
+            }
+
+            ast::ExprKind::Record(record) => {
+                let fields = record
+                    .fields
+                    .iter()
+                    .map(|field| {
+                        self.node(field.span, |this| {
+                            Doc::concat(vec![
+                                Doc::text(format!("{}: ", field.name)),
+                                this.expr(&field.value),
+                            ])
+                        })
+                    })
+                    .collect();
+
+                Doc::concat(vec![Doc::text(format!("{} ", record.name)), braces(fields)])
+            }
+
+            ast::ExprKind::Field(access) => Doc::concat(vec![
+                self.expr(&access.record),
+                Doc::text(format!(".{}", access.field)),
+            ]),
+
+            // Literals are kept as written so that things like hex integers and escapes survive
+            ast::ExprKind::Literal(_) | ast::ExprKind::Error => Doc::text(self.text(expr.span)),
+            ast::ExprKind::Var(var) => Doc::text(var.as_str()),
+        }
+    }
+
+    /// The arguments of an application, each of which goes on its own line if they don't fit
+    fn args(&mut self, args: &[ast::Expr]) -> Doc {
+        let mut docs = Vec::new();
+        for arg in args {
+            docs.push(Doc::Line);
+            docs.push(self.expr(arg));
+        }
+
+        Doc::nest(INDENT, Doc::concat(docs))
+    }
+
+    fn param(&mut self, pattern: &ast::Pattern, ty: &ast::Type) -> Doc {
+        Doc::concat(vec![self.pattern(pattern), Doc::text(": "), self.ty(ty)])
+    }
+
+    fn ty(&mut self, ty: &ast::Type) -> Doc {
+        self.node(ty.span, |this| this.ty_kind(ty))
+    }
+
+    fn ty_kind(&mut self, ty: &ast::Type) -> Doc {
+        match &ty.kind {
+            ast::TypeKind::Path(ty) => Doc::text(path(ty)),
+            ast::TypeKind::Bool => Doc::text("Bool"),
+            ast::TypeKind::Int => Doc::text("Int"),
+            ast::TypeKind::String => Doc::text("String"),
+            ast::TypeKind::Generic(name) => Doc::text(format!("'{}", name)),
+
+            ast::TypeKind::Tuple(elems) => {
+                let elems = elems.iter().map(|elem| self.ty(elem)).collect();
+                tuple(elems)
+            }
+
+            ast::TypeKind::List(elem) => {
+                Doc::concat(vec![Doc::text("List "), self.atomic_ty(elem)])
+            }
+
+            ast::TypeKind::Func(args, ret) => {
+                let mut docs = vec![Doc::text("fn(")];
+                for (idx, arg) in args.iter().enumerate() {
+                    if idx != 0 {
+                        docs.push(Doc::text(", "));
+                    }
+                    docs.push(self.ty(arg));
+                }
+                docs.extend(vec![Doc::text(") -> "), self.ty(ret)]);
+
+                Doc::concat(docs)
+            }
+
+            ast::TypeKind::Refined(refinement) => Doc::group(Doc::concat(vec![
+                Doc::text(format!("{{ {}: ", refinement.binder)),
+                self.ty(&refinement.ty),
+                Doc::text(" =>"),
+                Doc::nest(
+                    INDENT,
+                    Doc::concat(vec![Doc::Line, self.expr(&refinement.predicate)]),
+                ),
+                Doc::Line,
+                Doc::text("}"),
+            ])),
+        }
+    }
+
+    /// Function and list types take whatever follows them, so they're parenthesised
+    /// where types are written one after another
+    fn atomic_ty(&mut self, ty: &ast::Type) -> Doc {
+        match &ty.kind {
+            ast::TypeKind::Func(..) | ast::TypeKind::List(_) => {
+                Doc::concat(vec![Doc::text("("), self.ty(ty), Doc::text(")")])
+            }
+            _ => self.ty(ty),
+        }
+    }
+
+    fn pattern(&mut self, pattern: &ast::Pattern) -> Doc {
+        self.node(pattern.span, |this| this.pattern_kind(pattern))
+    }
+
+    fn pattern_kind(&mut self, pattern: &ast::Pattern) -> Doc {
+        match &pattern.kind {
+            ast::PatternKind::Literal(_) => Doc::text(self.text(pattern.span)),
+            ast::PatternKind::Path(pattern) => Doc::text(path(pattern)),
+            ast::PatternKind::Ident(name) => Doc::text(name.as_str()),
+
+            ast::PatternKind::Tuple(elems) => {
+                let elems = elems.iter().map(|elem| self.pattern(elem)).collect();
+                tuple(elems)
+            }
+
+            ast::PatternKind::Constructor(name, args) => {
+                let mut docs = vec![Doc::text(name.as_str())];
+                for arg in args {
+                    docs.push(Doc::text(" "));
+                    match &arg.kind {
+                        ast::PatternKind::Constructor(_, nested) if !nested.is_empty() => {
+                            docs.extend(vec![Doc::text("("), self.pattern(arg), Doc::text(")")]);
+                        }
+                        _ => docs.push(self.pattern(arg)),
+                    }
+                }
+
+                Doc::concat(docs)
+            }
+        }
+    }
+
+    fn text(&self, span: Span) -> &str {
+        &self.source[span.start..span.end]
+    }
+}
+
+fn docs(docs: &[String]) -> Doc {
+    Doc::concat(docs.iter().map(|doc| {
+        Doc::concat(vec![
+            Doc::text(format!("--- {}", doc).trim_end()),
+            Doc::HardLine,
+        ])
+    }))
+}
+
+fn tuple(elems: Vec<Doc>) -> Doc {
+    match elems.len() {
+        0 => Doc::text("()"),
+        1 => Doc::concat(vec![Doc::text("("), Doc::concat(elems), Doc::text(",)")]),
+
+        _ => {
+            let mut inner = Vec::new();
+            for (idx, elem) in elems.into_iter().enumerate() {
+                if idx != 0 {
+                    inner.extend(vec![Doc::text(","), Doc::Line]);
+                }
+                inner.push(elem);
+            }
+
+            Doc::group(Doc::concat(vec![
+                Doc::text("("),
+                Doc::nest(INDENT, Doc::concat(vec![Doc::SoftLine, Doc::concat(inner)])),
+                Doc::SoftLine,
+                Doc::text(")"),
+            ]))
+        }
+    }
+}
+
+fn list(elems: Vec<Doc>) -> Doc {
+    if elems.is_empty() {
+        return Doc::text("[]");
+    }
+
+    let mut inner = Vec::new();
+    for (idx, elem) in elems.into_iter().enumerate() {
+        if idx != 0 {
+            inner.extend(vec![Doc::text(","), Doc::Line]);
+        }
+        inner.push(elem);
+    }
+
+    Doc::group(Doc::concat(vec![
+        Doc::text("["),
+        Doc::nest(INDENT, Doc::concat(vec![Doc::SoftLine, Doc::concat(inner)])),
+        Doc::SoftLine,
+        Doc::text("]"),
+    ]))
+}
+
+/// The fields of a record, which go on their own lines if they don't fit on one
+fn braces(fields: Vec<Doc>) -> Doc {
+    if fields.is_empty() {
+        return Doc::text("{}");
+    }
+
+    let mut inner = Vec::new();
+    for (idx, field) in fields.into_iter().enumerate() {
+        if idx != 0 {
+            inner.extend(vec![Doc::text(","), Doc::Line]);
+        }
+        inner.push(field);
+    }
+
+    Doc::group(Doc::concat(vec![
+        Doc::text("{"),
+        Doc::nest(INDENT, Doc::concat(vec![Doc::Line, Doc::concat(inner)])),
+        Doc::Line,
+        Doc::text("}"),
+    ]))
+}
+
+fn path(path: &ast::Path) -> String {
+    let segments: Vec<&str> = path
+        .segments
+        .iter()
+        .map(|segment| segment.as_str())
+        .collect();
+    segments.join(".")
+}
+
+fn binary_op(op: ast::BinaryOp) -> &'static str {
+    match op {
+        ast::BinaryOp::Eq => "==",
+        ast::BinaryOp::NotEq => "!=",
+        ast::BinaryOp::Less => "<",
+        ast::BinaryOp::LessEq => "<=",
+        ast::BinaryOp::Greater => ">",
+        ast::BinaryOp::GreaterEq => ">=",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{lexer::Lexer, syntax};
+
+    fn format(source: &str, width: usize) -> String {
+        let items = syntax::lower(&syntax::parse(source));
+
+        let (mut errors, mut comments) = (Vec::new(), Vec::new());
+        Lexer::new(source, &mut errors)
+            .with_comments(&mut comments)
+            .for_each(drop);
+
+        super::format(source, &items, &comments, width)
+    }
+
+    /// Checks that `source` formats into `expected` and that formatting that again
+    /// leaves it as it is
+    fn golden(source: &str, expected: &str) {
+        let formatted = format(source, super::DEFAULT_WIDTH);
+        assert_eq!(formatted, expected);
+        assert_eq!(format(&formatted, super::DEFAULT_WIDTH), expected);
+    }
+
+    #[test]
+    fn canonical_layout() {
+        golden(
+            "fn   add x: Int y: { v: Int => v > 0 } -> Int =\n\
+            \x20   let t := (x, (1,), ());\n\
+            \x20   foo <| x 0xff (not True) t;\n\
+            \x20   match t with\n\
+            \x20   | (a, b, c) -> a\n\
+            \x20   | _ -> x\n\
+            module m =\n\
+            \x20   fn f x: 'a -> 'a = x\n\
+            end\n\
+            use m.f\n",
+            "fn add x: Int y: { v: Int => v > 0 } -> Int =\n\
+            \x20   let t := (x, (1,), ());\n\
+            \x20   foo <| x 0xff (not True) t;\n\
+            \x20   match t with\n\
+            \x20       | (a, b, c) -> a\n\
+            \x20       | _ -> x\n\
+            \n\
+            module m =\n\
+            \x20   fn f x: 'a -> 'a =\n\
+            \x20       x\n\
+            end\n\
+            \n\
+            use m.f\n",
+        );
+    }
+
+    #[test]
+    fn blank_lines_between_statements_are_kept() {
+        golden(
+            "fn f =\n    let a := 1;\n\n\n    let b := 2;\n    a\n",
+            "fn f =\n    let a := 1;\n\n    let b := 2;\n    a\n",
+        );
+    }
+
+    #[test]
+    fn comments_around_statements() {
+        golden(
+            "-- header\n\
+            \n\
+            --- Docs\n\
+            fn f x: Int =\n\
+            \x20   let s := 1;  -- trailing\n\
+            \x20   {- block\n\
+            \x20      comment -}\n\
+            \x20   x\n\
+            module m =\n\
+            \x20   fn g = 1\n\
+            \x20   -- last in module\n\
+            end\n\
+            -- eof\n",
+            "-- header\n\
+            \n\
+            --- Docs\n\
+            fn f x: Int =\n\
+            \x20   let s := 1; -- trailing\n\
+            \x20   {- block\n\
+            \x20      comment -}\n\
+            \x20   x\n\
+            \n\
+            module m =\n\
+            \x20   fn g =\n\
+            \x20       1\n\
+            \x20   -- last in module\n\
+            end\n\
+            -- eof\n",
+        );
+    }
+
+    #[test]
+    fn comments_within_expressions_stay_with_their_nodes() {
+        golden(
+            "fn f x: Int -> Int =\n\
+            \x20   let y := g x -- after x\n\
+            \x20       -- before h\n\
+            \x20       (h 1);\n\
+            \x20   let z :=\n\
+            \x20       -- explain\n\
+            \x20       add y {- inline -} 2;\n\
+            \x20   match z with\n\
+            \x20   | 0 -> 1 -- zero\n\
+            \x20   -- other\n\
+            \x20   | n -> n\n",
+            "fn f x: Int -> Int =\n\
+            \x20   let y :=\n\
+            \x20       g\n\
+            \x20           x -- after x\n\
+            \x20           -- before h\n\
+            \x20           (h 1);\n\
+            \x20   let z :=\n\
+            \x20       -- explain\n\
+            \x20       add y {- inline -} 2;\n\
+            \x20   match z with\n\
+            \x20       | 0 -> 1 -- zero\n\
+            \x20       -- other\n\
+            \x20       | n -> n\n",
+        );
+    }
+
+    #[test]
+    fn comments_within_type_definitions_stay_with_their_nodes() {
+        golden(
+            "type Shape =\n\
+            \x20   -- round\n\
+            \x20   | Circle Int\n\
+            \x20   | Rect Int Int -- w h\n\
+            type Point = {\n\
+            \x20   x: Int, -- across\n\
+            \x20   -- down\n\
+            \x20   y: Int\n\
+            }\n",
+            "type Shape =\n\
+            \x20   -- round\n\
+            \x20   | Circle Int\n\
+            \x20   | Rect Int Int -- w h\n\
+            \n\
+            type Point = {\n\
+            \x20   x: Int, -- across\n\
+            \x20   -- down\n\
+            \x20   y: Int\n\
+            }\n",
+        );
+    }
+
+    #[test]
+    fn line_comments_never_swallow_code() {
+        let formatted = format(
+            "fn f =\n    (add 1 -- one\n        2)\n",
+            super::DEFAULT_WIDTH,
+        );
+        for line in formatted.lines() {
+            if let Some(comment) = line.find("--") {
+                assert_eq!(&line[comment..], "-- one");
+            }
+        }
+        assert_eq!(format(&formatted, super::DEFAULT_WIDTH), formatted);
+    }
+
+    #[test]
+    fn groups_break_when_they_dont_fit() {
+        let source = "fn f =\n    foo <| (first, second, third) [1, 2, 3];\n";
+        assert_eq!(format(source, super::DEFAULT_WIDTH), source);
+        assert_eq!(
+            format(source, 20),
+            "fn f =\n\
+            \x20   foo <|\n\
+            \x20       (\n\
+            \x20           first,\n\
+            \x20           second,\n\
+            \x20           third\n\
+            \x20       )\n\
+            \x20       [1, 2, 3];\n",
+        );
+    }
+}
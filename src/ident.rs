@@ -0,0 +1,115 @@
+//! Interned identifiers
+//!
+//! Every identifier is interned once into a global table, which also holds the
+//! same string interned on the Datalog side. Copying, comparing and hashing an
+//! [`Ident`] are all free and lowering one into Datalog never copies its text
+
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Display, Formatter, Result as FmtResult},
+    ops::Deref,
+    sync::{LazyLock, Mutex},
+};
+use types::internment::{self, istring};
+
+static INTERNER: LazyLock<Mutex<Interner>> = LazyLock::new(|| Mutex::new(Interner::default()));
+
+#[derive(Default)]
+struct Interner {
+    ids: HashMap<&'static str, Ident>,
+    // Interned strings live for the rest of the program, so they're leaked
+    // to hand out `&'static str`s without holding the lock
+    strings: Vec<&'static str>,
+    istrings: Vec<istring>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct Ident(u32);
+
+impl Ident {
+    pub fn new(string: &str) -> Self {
+        let mut interner = INTERNER.lock().unwrap();
+        if let Some(&ident) = interner.ids.get(string) {
+            return ident;
+        }
+
+        let ident = Self(interner.strings.len() as u32);
+        let string: &'static str = Box::leak(string.to_owned().into_boxed_str());
+        interner.ids.insert(string, ident);
+        interner.strings.push(string);
+        interner
+            .istrings
+            .push(internment::intern(&string.to_owned()));
+
+        ident
+    }
+
+    pub fn as_str(self) -> &'static str {
+        INTERNER.lock().unwrap().strings[self.0 as usize]
+    }
+
+    /// The identifier as interned by Datalog, which shares its allocation with every other use
+    pub fn to_istring(self) -> istring {
+        INTERNER.lock().unwrap().istrings[self.0 as usize].clone()
+    }
+}
+
+impl Deref for Ident {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        self.as_str()
+    }
+}
+
+impl Debug for Ident {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl Display for Ident {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_strings_share_an_ident() {
+        let ident = Ident::new("interned");
+
+        assert_eq!(ident, Ident::new("interned"));
+        assert_ne!(ident, Ident::new("other"));
+        assert_eq!(ident.as_str(), "interned");
+        assert_eq!(format!("{} {:?}", ident, ident), "interned \"interned\"");
+    }
+
+    #[test]
+    fn interning_from_several_threads() {
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    (0..100)
+                        .map(|idx| Ident::new(&format!("thread_{}", idx)))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        let idents: Vec<_> = threads
+            .into_iter()
+            .map(|thread| thread.join().unwrap())
+            .collect();
+        for other in &idents[1..] {
+            assert_eq!(&idents[0], other);
+        }
+        for (idx, ident) in idents[0].iter().enumerate() {
+            assert_eq!(ident.as_str(), format!("thread_{}", idx));
+        }
+    }
+}
@@ -0,0 +1,295 @@
+//! The layout rule, which is what separates the statements of a function body
+//!
+//! Functions are applied by writing them next to their arguments, so `f x` on one
+//! line followed by `g y` on the next would otherwise read as `f x g y`. Instead
+//! the first token after a function's `=` sets the column of its body, and every
+//! later line starting at exactly that column begins a new statement, marked with a
+//! [`Token::Newline`]. Lines indented further continue the statement before them,
+//! and a line indented any less ends the body
+//!
+//! ```text
+//! fn main =
+//!     let x := 5;
+//!     add x 5
+//! ```
+
+use crate::lexer::{LexError, Spanned, Token};
+
+pub struct Layout<'a, I> {
+    source: &'a str,
+    tokens: I,
+    body: Option<Body>,
+    /// Whether the next `=` is the end of an item's header
+    in_header: bool,
+    /// Whether the next token is the first of a body
+    opening: bool,
+    /// Whether the last token can be followed by a type, where `fn` starts a
+    /// function type instead of an item
+    before_type: bool,
+    /// The number of delimiters currently open, nothing within them is ever split
+    depth: usize,
+    last_end: Option<usize>,
+    pending: Option<Spanned<'a>>,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Body {
+    column: usize,
+    depth: usize,
+}
+
+impl<'a, I> Layout<'a, I>
+where
+    I: Iterator<Item = Result<Spanned<'a>, LexError>>,
+{
+    /// Applies the layout rule to `tokens`, which must have been lexed from `source`
+    pub fn new(source: &'a str, tokens: I) -> Self {
+        Self {
+            source,
+            tokens,
+            body: None,
+            in_header: false,
+            opening: false,
+            before_type: false,
+            depth: 0,
+            last_end: None,
+            pending: None,
+        }
+    }
+
+    fn column(&self, pos: usize) -> usize {
+        let line_start = self.source[..pos]
+            .rfind('\n')
+            .map_or(0, |newline| newline + 1);
+        self.source[line_start..pos].chars().count()
+    }
+}
+
+impl<'a, I> Iterator for Layout<'a, I>
+where
+    I: Iterator<Item = Result<Spanned<'a>, LexError>>,
+{
+    type Item = Result<Spanned<'a>, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(token) = self.pending.take() {
+            return Some(Ok(token));
+        }
+
+        let (start, token, end) = match self.tokens.next()? {
+            Ok(token) => token,
+            Err(error) => return Some(Err(error)),
+        };
+
+        let first_on_line = self
+            .last_end
+            .is_none_or(|last_end| self.source[last_end..start].contains('\n'));
+        let column = self.column(start);
+        self.last_end = Some(end);
+
+        let is_type = token == Token::Fn && self.before_type;
+        self.before_type = matches!(
+            token,
+            Token::Colon | Token::Arrow | Token::LeftParen | Token::Comma
+        );
+
+        let mut newline = false;
+        if self.opening {
+            self.opening = false;
+            self.body = Some(Body {
+                column,
+                depth: self.depth,
+            });
+        } else if let Some(body) = self.body {
+            let at_body_level = first_on_line && self.depth == body.depth;
+
+            if (ends_body(&token) && !is_type) || (at_body_level && column < body.column) {
+                self.body = None;
+            } else if at_body_level && column == body.column && starts_statement(&token) {
+                newline = true;
+            }
+        }
+
+        match token {
+            Token::LeftParen | Token::LeftBrace | Token::LeftBracket => self.depth += 1,
+            Token::RightParen | Token::RightBrace | Token::RightBracket => {
+                self.depth = self.depth.saturating_sub(1)
+            }
+            Token::Fn | Token::Module if !is_type => self.in_header = true,
+            Token::Eq if self.in_header => {
+                self.in_header = false;
+                self.opening = true;
+            }
+            _ => {}
+        }
+
+        if newline {
+            self.pending = Some((start, token, end));
+            Some(Ok((start, Token::Newline, start)))
+        } else {
+            Some(Ok((start, token, end)))
+        }
+    }
+}
+
+/// Whether the token can only be the start of another item
+fn ends_body(token: &Token<'_>) -> bool {
+    matches!(
+        token,
+        Token::Fn | Token::Module | Token::End | Token::Use | Token::Type | Token::DocComment(_)
+    )
+}
+
+fn starts_statement(token: &Token<'_>) -> bool {
+    matches!(
+        token,
+        Token::Let
+            | Token::Match
+            | Token::If
+            | Token::Ensure
+            | Token::Not
+            | Token::Backslash
+            | Token::True
+            | Token::False
+            | Token::LeftParen
+            | Token::LeftBracket
+            | Token::Ident(_)
+            | Token::StringLit(_)
+            | Token::IntLit(_)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+
+    /// The source of every run of tokens that the layout rule puts newlines between
+    fn statements(source: &str) -> Vec<&str> {
+        let mut errors = Vec::new();
+        let mut statements = Vec::new();
+        let mut current: Option<(usize, usize)> = None;
+
+        for token in Layout::new(source, Lexer::new(source, &mut errors)) {
+            match token.unwrap() {
+                (_, Token::Newline, _) => statements.extend(current.take()),
+                (start, _, end) => {
+                    current = Some(current.map_or((start, end), |(first, _)| (first, end)));
+                }
+            }
+        }
+        statements.extend(current);
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        statements
+            .into_iter()
+            .map(|(start, end)| &source[start..end])
+            .collect()
+    }
+
+    #[test]
+    fn statements_start_at_the_body_column() {
+        assert_eq!(
+            statements("fn main =\n    let x := 5;\n    add x 5\n    x\n"),
+            ["fn main =\n    let x := 5;", "add x 5", "x"],
+        );
+    }
+
+    #[test]
+    fn the_first_token_sets_the_column() {
+        assert_eq!(
+            statements("fn main = add 1\n          x\n    y\n"),
+            ["fn main = add 1", "x\n    y"],
+        );
+    }
+
+    #[test]
+    fn continuation_lines() {
+        assert_eq!(
+            statements(
+                "fn main =\n\
+                \x20   let x := add 1\n\
+                \x20       2;\n\
+                \x20   add x\n\
+                \x20     5\n\
+                \x20   |> f\n\
+                \x20   x\n",
+            ),
+            [
+                "fn main =\n    let x := add 1\n        2;",
+                "add x\n      5\n    |> f",
+                "x",
+            ],
+        );
+    }
+
+    #[test]
+    fn delimiters_are_never_split() {
+        assert_eq!(
+            statements(
+                "fn main =\n\
+                \x20   f (1,\n\
+                \x20   2) [3,\n\
+                \x204]\n\
+                \x20   Point {\n\
+                \x20   x: 1 }\n",
+            ),
+            [
+                "fn main =\n    f (1,\n    2) [3,\n 4]",
+                "Point {\n    x: 1 }"
+            ],
+        );
+    }
+
+    #[test]
+    fn match_arms_continue_their_statement() {
+        assert_eq!(
+            statements(
+                "fn check x: Int -> Bool =\n\
+                \x20   match x with\n\
+                \x20   | 0 -> False\n\
+                \x20   | _ -> True\n\
+                \x20   x\n",
+            ),
+            [
+                "fn check x: Int -> Bool =\n    match x with\n    | 0 -> False\n    | _ -> True",
+                "x",
+            ],
+        );
+    }
+
+    #[test]
+    fn dedenting_ends_the_body() {
+        assert_eq!(
+            statements("fn f =\n    a\n  b\n    c\n"),
+            ["fn f =\n    a\n  b\n    c"],
+        );
+    }
+
+    #[test]
+    fn nested_bodies() {
+        assert_eq!(
+            statements(
+                "module m =\n\
+                \x20   fn f =\n\
+                \x20       a\n\
+                \x20       b\n\
+                \x20   fn g x: fn(Int) -> Int =\n\
+                \x20     x 1\n\
+                \x20     \\y: fn(Int) -> Int -> y\n\
+                \x20     2\n\
+                end\n\
+                fn h =\n\
+                \x20 c\n\
+                \x20 d\n",
+            ),
+            [
+                "module m =\n    fn f =\n        a",
+                "b\n    fn g x: fn(Int) -> Int =\n      x 1",
+                "\\y: fn(Int) -> Int -> y",
+                "2\nend\nfn h =\n  c",
+                "d",
+            ],
+        );
+    }
+}
@@ -0,0 +1,786 @@
+//! The lexer, turning source text into the token stream consumed by the parser
+//!
+//! Comments are skipped entirely except for doc comments, which are passed along
+//! to be attached to the item that follows them. Nothing the lexer runs into is
+//! fatal, malformed input is recorded as a [`LexError`] and lexing carries on
+//!
+//! ```text
+//! -- A line comment
+//! {- A block comment, {- which may be nested -} -}
+//! --- A doc comment
+//! ```
+
+use crate::ast::Span;
+use std::{
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+};
+
+pub type Spanned<'a> = (usize, Token<'a>, usize);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token<'a> {
+    Fn,
+    Module,
+    End,
+    Use,
+    Type,
+    Let,
+    Match,
+    With,
+    If,
+    Then,
+    Else,
+    Ensure,
+    Not,
+    True,
+    False,
+    Int,
+    Bool,
+    String,
+    List,
+
+    Eq,
+    EqEq,
+    NotEq,
+    Less,
+    LessEq,
+    Greater,
+    GreaterEq,
+    Colon,
+    ColonEq,
+    Semicolon,
+    Arrow,
+    FatArrow,
+    Pipe,
+    LeftPipe,
+    RightPipe,
+    LeftParen,
+    RightParen,
+    LeftBrace,
+    RightBrace,
+    LeftBracket,
+    RightBracket,
+    Comma,
+    Dot,
+    Quote,
+    Backslash,
+
+    Ident(&'a str),
+    /// A string literal with its escapes already decoded
+    StringLit(String),
+    IntLit(i64),
+    /// The text of a doc comment without its leading `---` and the space following it
+    DocComment(&'a str),
+    /// The start of a new statement, never lexed from the source but inserted
+    /// by [`Layout`](crate::layout::Layout)
+    Newline,
+}
+
+impl Display for Token<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Ident(ident) => f.write_str(ident),
+            Self::StringLit(string) => write!(f, "{:?}", string),
+            Self::IntLit(int) => write!(f, "{}", int),
+            Self::DocComment(doc) => write!(f, "--- {}", doc),
+            Self::Newline => f.write_str("newline"),
+
+            token => {
+                let text = KEYWORDS
+                    .iter()
+                    .chain(SYMBOLS)
+                    .find(|(_, keyword)| keyword == token)
+                    .map_or("", |&(text, _)| text);
+
+                f.write_str(text)
+            }
+        }
+    }
+}
+
+const KEYWORDS: &[(&str, Token<'static>)] = &[
+    ("fn", Token::Fn),
+    ("module", Token::Module),
+    ("end", Token::End),
+    ("use", Token::Use),
+    ("type", Token::Type),
+    ("let", Token::Let),
+    ("match", Token::Match),
+    ("with", Token::With),
+    ("if", Token::If),
+    ("then", Token::Then),
+    ("else", Token::Else),
+    ("ensure", Token::Ensure),
+    ("not", Token::Not),
+    ("True", Token::True),
+    ("False", Token::False),
+    ("Int", Token::Int),
+    ("Bool", Token::Bool),
+    ("String", Token::String),
+    ("List", Token::List),
+];
+
+/// Symbols that are a prefix of another come after it so that the longest is always taken
+const SYMBOLS: &[(&str, Token<'static>)] = &[
+    (":=", Token::ColonEq),
+    ("->", Token::Arrow),
+    ("=>", Token::FatArrow),
+    ("==", Token::EqEq),
+    ("!=", Token::NotEq),
+    ("<=", Token::LessEq),
+    (">=", Token::GreaterEq),
+    ("<|", Token::LeftPipe),
+    ("|>", Token::RightPipe),
+    ("=", Token::Eq),
+    ("<", Token::Less),
+    (">", Token::Greater),
+    (":", Token::Colon),
+    (";", Token::Semicolon),
+    ("|", Token::Pipe),
+    ("(", Token::LeftParen),
+    (")", Token::RightParen),
+    ("{", Token::LeftBrace),
+    ("}", Token::RightBrace),
+    ("[", Token::LeftBracket),
+    ("]", Token::RightBracket),
+    (",", Token::Comma),
+    (".", Token::Dot),
+    ("'", Token::Quote),
+    ("\\", Token::Backslash),
+];
+
+/// A line or block comment, `text` includes its delimiters
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Comment<'a> {
+    pub text: &'a str,
+    pub span: Span,
+}
+
+pub struct Lexer<'a, 'e> {
+    source: &'a str,
+    pos: usize,
+    errors: &'e mut Vec<LexError>,
+    comments: Option<&'e mut Vec<Comment<'a>>>,
+}
+
+impl<'a, 'e> Lexer<'a, 'e> {
+    /// Creates a lexer over `source`, anything wrong with the source is pushed onto `errors`
+    pub fn new(source: &'a str, errors: &'e mut Vec<LexError>) -> Self {
+        Self {
+            source,
+            pos: 0,
+            errors,
+            comments: None,
+        }
+    }
+
+    /// Keeps every comment the lexer skips over in `comments` instead of throwing them away
+    pub fn with_comments(mut self, comments: &'e mut Vec<Comment<'a>>) -> Self {
+        self.comments = Some(comments);
+        self
+    }
+
+    fn comment(&mut self, start: usize) {
+        let text = &self.source[start..self.pos];
+        if let Some(comments) = self.comments.as_mut() {
+            comments.push(Comment {
+                text: text.trim_end(),
+                span: Span::new(start, start + text.trim_end().len()),
+            });
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.source[self.pos..]
+    }
+
+    fn error(&mut self, kind: LexErrorKind, start: usize, end: usize) {
+        self.errors.push(LexError::new(kind, Span::new(start, end)));
+    }
+
+    /// Advances while `predicate` holds, returning everything that was skipped
+    fn eat_while<P>(&mut self, predicate: P) -> &'a str
+    where
+        P: Fn(char) -> bool,
+    {
+        let rest = self.rest();
+        let len = rest.find(|chr| !predicate(chr)).unwrap_or(rest.len());
+        self.pos += len;
+
+        &rest[..len]
+    }
+
+    /// Skips a (possibly nested) block comment, the opening `{-` has already been consumed
+    fn block_comment(&mut self, start: usize) {
+        let mut depth = 1;
+
+        while depth > 0 {
+            let rest = self.rest();
+            if rest.starts_with("{-") {
+                depth += 1;
+                self.pos += 2;
+            } else if rest.starts_with("-}") {
+                depth -= 1;
+                self.pos += 2;
+            } else if let Some(chr) = rest.chars().next() {
+                self.pos += chr.len_utf8();
+            } else {
+                self.error(LexErrorKind::UnterminatedComment, start, self.pos);
+                return;
+            }
+        }
+    }
+
+    /// Lexes a string literal, the opening quote has already been consumed. Strings can't
+    /// span lines, so one that's never closed is reported at its opening quote and taken
+    /// up to the end of its line for lexing to carry on from the next
+    fn string(&mut self, start: usize) -> Token<'a> {
+        let rest = self.rest();
+        let line = &rest[..rest.find('\n').unwrap_or(rest.len())];
+
+        let mut escaped = false;
+        let close = line.find(|chr| {
+            let closes = !escaped && chr == '"';
+            escaped = !escaped && chr == '\\';
+            closes
+        });
+
+        match close {
+            Some(len) => {
+                self.pos += len + 1;
+
+                let contents = &line[..len];
+                match unescape(contents, start + 1) {
+                    Ok(decoded) => Token::StringLit(decoded),
+                    Err(error) => {
+                        self.errors.push(error);
+                        Token::StringLit(contents.to_owned())
+                    }
+                }
+            }
+
+            None => {
+                let contents = line.strip_suffix('\r').unwrap_or(line);
+                self.pos += contents.len();
+                self.error(LexErrorKind::UnterminatedString, start, start + 1);
+
+                let decoded = unescape(contents, start + 1).unwrap_or_else(|_| contents.to_owned());
+                Token::StringLit(decoded)
+            }
+        }
+    }
+
+    /// Lexes an integer literal, any sign has already been consumed
+    fn int(&mut self, start: usize, negative: bool) -> Token<'a> {
+        // Everything alphanumeric is taken so that `0b102` is one bad literal instead of two good ones
+        let digits_start = self.pos;
+        let literal = self.eat_while(|chr| chr.is_ascii_alphanumeric() || chr == '_');
+
+        let (radix, digits) = match literal.get(..2) {
+            Some("0x") => (16, &literal[2..]),
+            Some("0o") => (8, &literal[2..]),
+            Some("0b") => (2, &literal[2..]),
+            _ => (10, literal),
+        };
+        let prefix = literal.len() - digits.len();
+
+        let mut value: Option<i128> = Some(0);
+        let mut any_digits = false;
+        for (idx, chr) in digits.char_indices() {
+            if chr == '_' {
+                continue;
+            }
+
+            match chr.to_digit(radix) {
+                Some(digit) => {
+                    any_digits = true;
+                    value = value
+                        .and_then(|value| value.checked_mul(radix as i128))
+                        .and_then(|value| value.checked_add(digit as i128));
+                }
+
+                None => {
+                    let digit_start = digits_start + prefix + idx;
+                    self.error(
+                        LexErrorKind::InvalidDigit { digit: chr, radix },
+                        digit_start,
+                        digit_start + chr.len_utf8(),
+                    );
+
+                    return Token::IntLit(0);
+                }
+            }
+        }
+
+        if !any_digits {
+            self.error(LexErrorKind::MissingDigits, start, self.pos);
+            return Token::IntLit(0);
+        }
+
+        let value = value.map(|value| if negative { -value } else { value });
+        match value.filter(|&value| i64::MIN as i128 <= value && value <= i64::MAX as i128) {
+            Some(value) => Token::IntLit(value as i64),
+
+            // Saturate so that the literal's sign at least stays the same
+            None => {
+                self.error(LexErrorKind::IntOutOfRange, start, self.pos);
+                Token::IntLit(if negative { i64::MIN } else { i64::MAX })
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for Lexer<'a, '_> {
+    /// The parser expects a fallible stream, but errors are collected on the side
+    /// instead so that one bad character doesn't stop everything
+    type Item = Result<Spanned<'a>, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.eat_while(char::is_whitespace);
+
+            let start = self.pos;
+            let rest = self.rest();
+            let chr = rest.chars().next()?;
+
+            let token = if rest.starts_with("---") && !rest.starts_with("----") {
+                self.pos += 3;
+                let doc = self.eat_while(|chr| chr != '\n');
+
+                Token::DocComment(doc.strip_prefix(' ').unwrap_or(doc).trim_end())
+            } else if rest.starts_with("--") {
+                self.eat_while(|chr| chr != '\n');
+                self.comment(start);
+                continue;
+            } else if rest.starts_with("{-") {
+                self.pos += 2;
+                self.block_comment(start);
+                self.comment(start);
+                continue;
+            } else if chr == '"' {
+                self.pos += 1;
+                self.string(start)
+            } else if chr.is_ascii_digit() {
+                self.int(start, false)
+            } else if chr == '-' && rest[1..].starts_with(|chr: char| chr.is_ascii_digit()) {
+                self.pos += 1;
+                self.int(start, true)
+            } else if chr.is_ascii_alphabetic() || chr == '_' {
+                let ident = self.eat_while(|chr| chr.is_ascii_alphanumeric() || chr == '_');
+
+                KEYWORDS
+                    .iter()
+                    .find(|&&(keyword, _)| keyword == ident)
+                    .map_or(Token::Ident(ident), |(_, keyword)| keyword.clone())
+            } else if let Some((symbol, token)) =
+                SYMBOLS.iter().find(|(symbol, _)| rest.starts_with(symbol))
+            {
+                self.pos += symbol.len();
+                token.clone()
+            } else {
+                self.pos += chr.len_utf8();
+                self.error(LexErrorKind::InvalidCharacter(chr), start, self.pos);
+                continue;
+            };
+
+            return Some(Ok((start, token, self.pos)));
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexError {
+    pub kind: LexErrorKind,
+    pub span: Span,
+}
+
+impl LexError {
+    pub const fn new(kind: LexErrorKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+impl Display for LexError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match &self.kind {
+            LexErrorKind::UnterminatedString => f.write_str("Unterminated string literal"),
+            LexErrorKind::UnterminatedComment => f.write_str("Unterminated block comment"),
+            LexErrorKind::InvalidCharacter(chr) => {
+                write!(f, "Invalid character `{}`", chr.escape_debug())
+            }
+            LexErrorKind::InvalidDigit { digit, radix } => write!(
+                f,
+                "Invalid digit `{}` in a base {} integer literal",
+                digit.escape_debug(),
+                radix,
+            ),
+            LexErrorKind::MissingDigits => f.write_str("Integer literal has no digits"),
+            LexErrorKind::IntOutOfRange => write!(
+                f,
+                "Integer literal is out of range, integers must be between {} and {}",
+                i64::MIN,
+                i64::MAX,
+            ),
+            LexErrorKind::UnknownEscape(escape) => {
+                write!(f, "Unknown escape sequence `\\{}`", escape.escape_debug())
+            }
+            LexErrorKind::InvalidUnicodeEscape => f.write_str(
+                "Invalid unicode escape, expected `\\u{...}` with one to six hex digits of a unicode scalar value",
+            ),
+        }
+    }
+}
+
+impl Error for LexError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LexErrorKind {
+    UnterminatedString,
+    UnterminatedComment,
+    InvalidCharacter(char),
+    InvalidDigit { digit: char, radix: u32 },
+    MissingDigits,
+    IntOutOfRange,
+    UnknownEscape(char),
+    InvalidUnicodeEscape,
+}
+
+/// Decodes the escape sequences within the contents of a string literal,
+/// `start` is the offset of `contents` within the source file
+pub fn unescape(contents: &str, start: usize) -> Result<String, LexError> {
+    let mut decoded = String::with_capacity(contents.len());
+
+    let mut chars = contents.char_indices();
+    while let Some((idx, chr)) = chars.next() {
+        if chr != '\\' {
+            decoded.push(chr);
+            continue;
+        }
+
+        let escape = match chars.next() {
+            Some((_, escape)) => escape,
+            None => {
+                let span = Span::new(start + idx, start + contents.len());
+                return Err(LexError::new(LexErrorKind::UnterminatedString, span));
+            }
+        };
+
+        match escape {
+            'n' => decoded.push('\n'),
+            't' => decoded.push('\t'),
+            'r' => decoded.push('\r'),
+            '0' => decoded.push('\0'),
+            '"' => decoded.push('"'),
+            '\\' => decoded.push('\\'),
+
+            'u' => {
+                let rest = chars.as_str();
+                let digits = rest
+                    .strip_prefix('{')
+                    .and_then(|rest| rest.find('}').map(|end| &rest[..end]));
+
+                let unicode = digits
+                    .filter(|digits| {
+                        (1..=6).contains(&digits.len())
+                            && digits.chars().all(|digit| digit.is_ascii_hexdigit())
+                    })
+                    .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+                    .and_then(char::from_u32);
+
+                match (unicode, digits) {
+                    (Some(unicode), Some(digits)) => {
+                        decoded.push(unicode);
+
+                        // Skip over the braces and digits
+                        for _ in 0..digits.chars().count() + 2 {
+                            chars.next();
+                        }
+                    }
+
+                    (_, digits) => {
+                        let len = digits.map_or(0, |digits| digits.len() + 2);
+                        let span = Span::new(start + idx, start + idx + 2 + len);
+                        return Err(LexError::new(LexErrorKind::InvalidUnicodeEscape, span));
+                    }
+                }
+            }
+
+            escape => {
+                let span = Span::new(start + idx, start + idx + 1 + escape.len_utf8());
+                return Err(LexError::new(LexErrorKind::UnknownEscape(escape), span));
+            }
+        }
+    }
+
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lexes all of `source`, returning its tokens along with any errors
+    fn lex(source: &str) -> (Vec<Token<'_>>, Vec<LexError>) {
+        let mut errors = Vec::new();
+        let tokens = Lexer::new(source, &mut errors)
+            .map(|token| token.unwrap().1)
+            .collect();
+
+        (tokens, errors)
+    }
+
+    fn string(contents: &str) -> Token<'static> {
+        Token::StringLit(contents.to_owned())
+    }
+
+    #[test]
+    fn escapes() {
+        let (tokens, errors) = lex(r#""a\n\t\r\0\"\\b" "\u{1F600}\u{e9}""#);
+
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(
+            tokens,
+            [string("a\n\t\r\0\"\\b"), string("\u{1F600}\u{e9}")]
+        );
+    }
+
+    #[test]
+    fn unknown_escape() {
+        let (tokens, errors) = lex(r#"x := "bad \q""#);
+
+        assert_eq!(tokens[2], string(r"bad \q"));
+        assert_eq!(
+            errors,
+            [LexError::new(
+                LexErrorKind::UnknownEscape('q'),
+                Span::new(10, 12)
+            )],
+        );
+    }
+
+    #[test]
+    fn invalid_unicode_escapes() {
+        for source in [
+            r#""\u{}""#,
+            r#""\u{1234567}""#,
+            r#""\u{d800}""#,
+            r#""\u12""#,
+        ] {
+            let (_, errors) = lex(source);
+
+            assert_eq!(errors.len(), 1, "{}", source);
+            assert_eq!(
+                errors[0].kind,
+                LexErrorKind::InvalidUnicodeEscape,
+                "{}",
+                source
+            );
+            assert_eq!(errors[0].span.start, 1, "{}", source);
+        }
+    }
+
+    #[test]
+    fn unterminated_string() {
+        let (tokens, errors) = lex(r#""abc"#);
+
+        assert_eq!(tokens, [string("abc")]);
+        assert_eq!(
+            errors,
+            [LexError::new(
+                LexErrorKind::UnterminatedString,
+                Span::new(0, 1)
+            )],
+        );
+    }
+
+    #[test]
+    fn unterminated_string_ends_with_its_line() {
+        let source =
+            "fn a = \"abc\r\nfn b = 0b102\nfn c = 99999999999999999999\nfn d = \"bad \\q\"\n";
+        let (tokens, errors) = lex(source);
+
+        assert_eq!(
+            &tokens[..5],
+            [
+                Token::Fn,
+                Token::Ident("a"),
+                Token::Eq,
+                string("abc"),
+                Token::Fn
+            ]
+        );
+
+        let quote = source.find('"').unwrap();
+        let digit = source.find("2\n").unwrap();
+        let int = source.find("999").unwrap();
+        let escape = source.find("\\q").unwrap();
+        assert_eq!(
+            errors,
+            [
+                LexError::new(
+                    LexErrorKind::UnterminatedString,
+                    Span::new(quote, quote + 1)
+                ),
+                LexError::new(
+                    LexErrorKind::InvalidDigit {
+                        digit: '2',
+                        radix: 2,
+                    },
+                    Span::new(digit, digit + 1),
+                ),
+                LexError::new(LexErrorKind::IntOutOfRange, Span::new(int, int + 20)),
+                LexError::new(
+                    LexErrorKind::UnknownEscape('q'),
+                    Span::new(escape, escape + 2)
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn comments_are_skipped() {
+        let (tokens, errors) = lex("x -- a comment\n{- a {- nested -} comment -} y");
+
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(tokens, [Token::Ident("x"), Token::Ident("y")]);
+    }
+
+    #[test]
+    fn comments_are_collected() {
+        let source = "x -- line  \n{- block -}";
+        let (mut errors, mut comments) = (Vec::new(), Vec::new());
+        let tokens: Vec<_> = Lexer::new(source, &mut errors)
+            .with_comments(&mut comments)
+            .collect();
+
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(
+            comments,
+            [
+                Comment {
+                    text: "-- line",
+                    span: Span::new(2, 9),
+                },
+                Comment {
+                    text: "{- block -}",
+                    span: Span::new(12, 23),
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn doc_comments() {
+        let (tokens, _) = lex("--- Some docs  \n---Tight\n---- Not docs\nfn");
+
+        assert_eq!(
+            tokens,
+            [
+                Token::DocComment("Some docs"),
+                Token::DocComment("Tight"),
+                Token::Fn,
+            ],
+        );
+    }
+
+    #[test]
+    fn unterminated_block_comment() {
+        let (tokens, errors) = lex("x {- {- -}");
+
+        assert_eq!(tokens, [Token::Ident("x")]);
+        assert_eq!(
+            errors,
+            [LexError::new(
+                LexErrorKind::UnterminatedComment,
+                Span::new(2, 10)
+            )],
+        );
+    }
+
+    #[test]
+    fn int_literals() {
+        let (tokens, errors) =
+            lex("0 1_000 0xFF 0o17 0b1010 -5 -0x10 9223372036854775807 -9223372036854775808");
+
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(
+            tokens,
+            [
+                Token::IntLit(0),
+                Token::IntLit(1000),
+                Token::IntLit(255),
+                Token::IntLit(15),
+                Token::IntLit(10),
+                Token::IntLit(-5),
+                Token::IntLit(-16),
+                Token::IntLit(i64::MAX),
+                Token::IntLit(i64::MIN),
+            ],
+        );
+    }
+
+    #[test]
+    fn ints_out_of_range_saturate() {
+        let (tokens, errors) = lex("9223372036854775808 -9223372036854775809");
+
+        assert_eq!(tokens, [Token::IntLit(i64::MAX), Token::IntLit(i64::MIN)]);
+        assert_eq!(
+            errors,
+            [
+                LexError::new(LexErrorKind::IntOutOfRange, Span::new(0, 19)),
+                LexError::new(LexErrorKind::IntOutOfRange, Span::new(20, 40)),
+            ],
+        );
+    }
+
+    #[test]
+    fn invalid_digits() {
+        let (tokens, errors) = lex("0b102 0o8 12a");
+
+        assert_eq!(
+            tokens,
+            [Token::IntLit(0), Token::IntLit(0), Token::IntLit(0)]
+        );
+        assert_eq!(
+            errors,
+            [
+                LexError::new(
+                    LexErrorKind::InvalidDigit {
+                        digit: '2',
+                        radix: 2
+                    },
+                    Span::new(4, 5)
+                ),
+                LexError::new(
+                    LexErrorKind::InvalidDigit {
+                        digit: '8',
+                        radix: 8
+                    },
+                    Span::new(8, 9)
+                ),
+                LexError::new(
+                    LexErrorKind::InvalidDigit {
+                        digit: 'a',
+                        radix: 10
+                    },
+                    Span::new(12, 13)
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn missing_digits() {
+        let (tokens, errors) = lex("0x 0b__");
+
+        assert_eq!(tokens, [Token::IntLit(0), Token::IntLit(0)]);
+        assert_eq!(
+            errors,
+            [
+                LexError::new(LexErrorKind::MissingDigits, Span::new(0, 2)),
+                LexError::new(LexErrorKind::MissingDigits, Span::new(3, 7)),
+            ],
+        );
+    }
+}
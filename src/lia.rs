@@ -0,0 +1,613 @@
+//! A small decision procedure for linear integer arithmetic
+//!
+//! Formulas are put into disjunctive normal form and every conjunction is run through
+//! Fourier-Motzkin elimination, tightening constraints over the integers as it goes.
+//! This is sound for refutation (an unsatisfiable result is always correct) but incomplete,
+//! anything it can't decide within its limits is treated as satisfiable
+
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Formatter, Result as FmtResult},
+};
+
+/// The maximum number of conjunctions a formula may expand into
+const MAX_CUBES: usize = 256;
+/// The maximum number of constraints elimination may produce at once
+const MAX_CONSTRAINTS: usize = 512;
+
+pub type Var = u32;
+
+/// The linear combination `sum(coeff * var) + constant`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Linear {
+    pub terms: BTreeMap<Var, i128>,
+    pub constant: i128,
+}
+
+impl Linear {
+    pub fn constant(constant: i128) -> Self {
+        Self {
+            terms: BTreeMap::new(),
+            constant,
+        }
+    }
+
+    pub fn var(var: Var) -> Self {
+        let mut terms = BTreeMap::new();
+        terms.insert(var, 1);
+
+        Self { terms, constant: 0 }
+    }
+
+    pub fn is_constant(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    pub fn add(&self, other: &Self) -> Option<Self> {
+        let mut sum = self.clone();
+        for (&var, &coeff) in other.terms.iter() {
+            let entry = sum.terms.entry(var).or_insert(0);
+            *entry = entry.checked_add(coeff)?;
+
+            if *entry == 0 {
+                sum.terms.remove(&var);
+            }
+        }
+        sum.constant = sum.constant.checked_add(other.constant)?;
+
+        Some(sum)
+    }
+
+    pub fn scale(&self, factor: i128) -> Option<Self> {
+        if factor == 0 {
+            return Some(Self::default());
+        }
+
+        let mut terms = BTreeMap::new();
+        for (&var, &coeff) in self.terms.iter() {
+            terms.insert(var, coeff.checked_mul(factor)?);
+        }
+
+        Some(Self {
+            terms,
+            constant: self.constant.checked_mul(factor)?,
+        })
+    }
+
+    pub fn sub(&self, other: &Self) -> Option<Self> {
+        self.add(&other.scale(-1)?)
+    }
+
+    /// Formats the combination using the given names for each variable
+    pub fn display<'a>(&'a self, names: &'a [String]) -> impl Display + 'a {
+        DisplayLinear {
+            linear: self,
+            names,
+        }
+    }
+}
+
+struct DisplayLinear<'a> {
+    linear: &'a Linear,
+    names: &'a [String],
+}
+
+impl Display for DisplayLinear<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let mut first = true;
+        for (&var, &coeff) in self.linear.terms.iter() {
+            let name = self
+                .names
+                .get(var as usize)
+                .map_or("_", |name| name.as_str());
+
+            match (first, coeff) {
+                (true, 1) => write!(f, "{}", name)?,
+                (true, -1) => write!(f, "-{}", name)?,
+                (true, coeff) => write!(f, "{} * {}", coeff, name)?,
+                (false, 1) => write!(f, " + {}", name)?,
+                (false, -1) => write!(f, " - {}", name)?,
+                (false, coeff) if coeff < 0 => write!(f, " - {} * {}", -coeff, name)?,
+                (false, coeff) => write!(f, " + {} * {}", coeff, name)?,
+            }
+            first = false;
+        }
+
+        match (first, self.linear.constant) {
+            (true, constant) => write!(f, "{}", constant),
+            (false, 0) => Ok(()),
+            (false, constant) if constant < 0 => write!(f, " - {}", -constant),
+            (false, constant) => write!(f, " + {}", constant),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Relation {
+    Eq,
+    NotEq,
+    Less,
+    LessEq,
+    Greater,
+    GreaterEq,
+}
+
+impl Relation {
+    pub fn negate(self) -> Self {
+        match self {
+            Self::Eq => Self::NotEq,
+            Self::NotEq => Self::Eq,
+            Self::Less => Self::GreaterEq,
+            Self::LessEq => Self::Greater,
+            Self::Greater => Self::LessEq,
+            Self::GreaterEq => Self::Less,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Eq => "==",
+            Self::NotEq => "!=",
+            Self::Less => "<",
+            Self::LessEq => "<=",
+            Self::Greater => ">",
+            Self::GreaterEq => ">=",
+        }
+    }
+}
+
+/// The comparison `lhs <relation> rhs`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Atom {
+    pub lhs: Linear,
+    pub relation: Relation,
+    pub rhs: Linear,
+}
+
+impl Atom {
+    pub fn new(lhs: Linear, relation: Relation, rhs: Linear) -> Self {
+        Self { lhs, relation, rhs }
+    }
+
+    /// Turns the atom into a disjunction of conjunctions of `linear <= 0` constraints
+    fn constraints(&self) -> Option<Vec<Vec<Linear>>> {
+        let one = Linear::constant(1);
+        let diff = self.lhs.sub(&self.rhs)?;
+        let rev = self.rhs.sub(&self.lhs)?;
+
+        let constraints = match self.relation {
+            Relation::LessEq => vec![vec![diff]],
+            Relation::GreaterEq => vec![vec![rev]],
+            Relation::Less => vec![vec![diff.add(&one)?]],
+            Relation::Greater => vec![vec![rev.add(&one)?]],
+            Relation::Eq => vec![vec![diff, rev]],
+            Relation::NotEq => vec![vec![diff.add(&one)?], vec![rev.add(&one)?]],
+        };
+
+        Some(constraints)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Formula {
+    True,
+    False,
+    Atom(Atom),
+    Not(Box<Formula>),
+    And(Vec<Formula>),
+    Or(Vec<Formula>),
+}
+
+impl Formula {
+    pub fn not(self) -> Self {
+        Self::Not(Box::new(self))
+    }
+
+    /// Formats the formula using the given names for each variable
+    pub fn display<'a>(&'a self, names: &'a [String]) -> impl Display + 'a {
+        DisplayFormula {
+            formula: self,
+            names,
+        }
+    }
+
+    /// Converts the formula into disjunctive normal form, returning `None` if
+    /// it's too large to handle
+    fn dnf(&self, negated: bool) -> Option<Vec<Vec<Linear>>> {
+        let cubes = match (self, negated) {
+            (Self::True, false) | (Self::False, true) => vec![Vec::new()],
+            (Self::True, true) | (Self::False, false) => Vec::new(),
+
+            (Self::Atom(atom), negated) => {
+                if negated {
+                    Atom::new(atom.lhs.clone(), atom.relation.negate(), atom.rhs.clone())
+                        .constraints()?
+                } else {
+                    atom.constraints()?
+                }
+            }
+
+            (Self::Not(inner), negated) => inner.dnf(!negated)?,
+
+            (Self::And(conjuncts), false) | (Self::Or(conjuncts), true) => {
+                let mut cubes = vec![Vec::new()];
+                for conjunct in conjuncts {
+                    let conjunct = conjunct.dnf(negated)?;
+
+                    let mut product = Vec::with_capacity(cubes.len() * conjunct.len());
+                    for cube in cubes.iter() {
+                        for other in conjunct.iter() {
+                            let mut combined = cube.clone();
+                            combined.extend(other.iter().cloned());
+                            product.push(combined);
+                        }
+                    }
+
+                    if product.len() > MAX_CUBES {
+                        return None;
+                    }
+                    cubes = product;
+                }
+
+                cubes
+            }
+
+            (Self::Or(disjuncts), false) | (Self::And(disjuncts), true) => {
+                let mut cubes = Vec::new();
+                for disjunct in disjuncts {
+                    cubes.extend(disjunct.dnf(negated)?);
+
+                    if cubes.len() > MAX_CUBES {
+                        return None;
+                    }
+                }
+
+                cubes
+            }
+        };
+
+        Some(cubes)
+    }
+}
+
+struct DisplayFormula<'a> {
+    formula: &'a Formula,
+    names: &'a [String],
+}
+
+impl Display for DisplayFormula<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let joined = |f: &mut Formatter<'_>, formulas: &[Formula], sep: &str| {
+            for (idx, formula) in formulas.iter().enumerate() {
+                if idx != 0 {
+                    f.write_str(sep)?;
+                }
+                write!(f, "({})", formula.display(self.names))?;
+            }
+
+            Ok(())
+        };
+
+        match self.formula {
+            Formula::True => f.write_str("True"),
+            Formula::False => f.write_str("False"),
+            Formula::Atom(atom) => write!(
+                f,
+                "{} {} {}",
+                atom.lhs.display(self.names),
+                atom.relation.as_str(),
+                atom.rhs.display(self.names),
+            ),
+            Formula::Not(inner) => write!(f, "not ({})", inner.display(self.names)),
+            Formula::And(conjuncts) => joined(f, conjuncts, " and "),
+            Formula::Or(disjuncts) => joined(f, disjuncts, " or "),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Verdict {
+    /// The goal holds in every case allowed by the hypotheses
+    Proven,
+    /// The goal holds in no case allowed by the hypotheses
+    Refuted,
+    /// The goal may or may not hold
+    Unknown,
+}
+
+/// Decides whether `goal` follows from `hypotheses`
+pub fn check(hypotheses: &[Formula], goal: &Formula) -> Verdict {
+    let assumed = Formula::And(hypotheses.to_vec());
+
+    if unsatisfiable(&Formula::And(vec![assumed.clone(), goal.clone().not()])) {
+        Verdict::Proven
+    } else if unsatisfiable(&Formula::And(vec![assumed, goal.clone()])) {
+        Verdict::Refuted
+    } else {
+        Verdict::Unknown
+    }
+}
+
+/// Returns `true` if the formula can never be satisfied by any assignment of integers
+pub fn unsatisfiable(formula: &Formula) -> bool {
+    match formula.dnf(false) {
+        Some(cubes) => cubes.into_iter().all(infeasible),
+        None => false,
+    }
+}
+
+/// Runs Fourier-Motzkin elimination over a conjunction of `linear <= 0` constraints
+fn infeasible(mut constraints: Vec<Linear>) -> bool {
+    loop {
+        let mut normalized = Vec::with_capacity(constraints.len());
+        for constraint in constraints {
+            let constraint = tighten(constraint);
+
+            if constraint.is_constant() {
+                if constraint.constant > 0 {
+                    return true;
+                }
+            } else {
+                normalized.push(constraint);
+            }
+        }
+        normalized.sort();
+        normalized.dedup();
+
+        if normalized.len() > MAX_CONSTRAINTS {
+            return false;
+        }
+
+        // Eliminate whichever variable creates the fewest new constraints
+        let var = normalized
+            .iter()
+            .flat_map(|constraint| constraint.terms.keys().copied())
+            .min_by_key(|&var| {
+                let (upper, lower) =
+                    normalized
+                        .iter()
+                        .fold((0, 0), |(upper, lower), c| match c.terms.get(&var) {
+                            Some(&coeff) if coeff > 0 => (upper + 1, lower),
+                            Some(_) => (upper, lower + 1),
+                            None => (upper, lower),
+                        });
+
+                upper * lower
+            });
+
+        let var = match var {
+            Some(var) => var,
+            None => return false,
+        };
+
+        let (mut upper, mut lower, mut rest) = (Vec::new(), Vec::new(), Vec::new());
+        for constraint in normalized {
+            match constraint.terms.get(&var).copied() {
+                Some(coeff) if coeff > 0 => upper.push((coeff, constraint)),
+                Some(coeff) => lower.push((-coeff, constraint)),
+                None => rest.push(constraint),
+            }
+        }
+
+        for (upper_coeff, upper) in upper.iter() {
+            for (lower_coeff, lower) in lower.iter() {
+                let combined = upper
+                    .scale(*lower_coeff)
+                    .zip(lower.scale(*upper_coeff))
+                    .and_then(|(upper, lower)| upper.add(&lower));
+
+                match combined {
+                    Some(combined) => rest.push(combined),
+                    // Give up rather than risk an unsound answer
+                    None => return false,
+                }
+            }
+        }
+
+        constraints = rest;
+    }
+}
+
+/// Divides a constraint through by the gcd of its coefficients, rounding
+/// the constant so that no integer solutions are lost
+fn tighten(mut constraint: Linear) -> Linear {
+    let divisor = constraint
+        .terms
+        .values()
+        .fold(0, |divisor, &coeff| gcd(divisor, coeff.abs()));
+
+    if divisor > 1 {
+        for coeff in constraint.terms.values_mut() {
+            *coeff /= divisor;
+        }
+
+        // `sum + c <= 0` becomes `sum / d + ceil(c / d) <= 0`
+        constraint.constant = -(-constraint.constant).div_euclid(divisor);
+    }
+
+    constraint
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn var(var: Var) -> Linear {
+        Linear::var(var)
+    }
+
+    fn int(int: i128) -> Linear {
+        Linear::constant(int)
+    }
+
+    fn atom(lhs: Linear, relation: Relation, rhs: Linear) -> Formula {
+        Formula::Atom(Atom::new(lhs, relation, rhs))
+    }
+
+    #[test]
+    fn proven() {
+        let hypotheses = [atom(var(0), Relation::Greater, int(0))];
+        assert_eq!(
+            check(&hypotheses, &atom(var(0), Relation::GreaterEq, int(1))),
+            Verdict::Proven,
+        );
+        assert_eq!(
+            check(&hypotheses, &atom(var(0), Relation::NotEq, int(0))),
+            Verdict::Proven,
+        );
+    }
+
+    #[test]
+    fn proven_through_elimination() {
+        // x < y and y < z imply x + 2 <= z
+        let hypotheses = [
+            atom(var(0), Relation::Less, var(1)),
+            atom(var(1), Relation::Less, var(2)),
+        ];
+        let goal = atom(var(0).add(&int(2)).unwrap(), Relation::LessEq, var(2));
+
+        assert_eq!(check(&hypotheses, &goal), Verdict::Proven);
+    }
+
+    #[test]
+    fn refuted() {
+        let hypotheses = [atom(var(0), Relation::Greater, int(5))];
+        assert_eq!(
+            check(&hypotheses, &atom(var(0), Relation::Less, int(3))),
+            Verdict::Refuted,
+        );
+        assert_eq!(
+            check(&hypotheses, &atom(var(0), Relation::Eq, int(5))),
+            Verdict::Refuted,
+        );
+    }
+
+    #[test]
+    fn unknown() {
+        let hypotheses = [atom(var(0), Relation::Greater, int(0))];
+        assert_eq!(
+            check(&hypotheses, &atom(var(0), Relation::Greater, int(5))),
+            Verdict::Unknown,
+        );
+        assert_eq!(
+            check(&[], &atom(var(0), Relation::Eq, var(1))),
+            Verdict::Unknown,
+        );
+    }
+
+    #[test]
+    fn disjunctions() {
+        let hypotheses = [Formula::Or(vec![
+            atom(var(0), Relation::Less, int(0)),
+            atom(var(0), Relation::Greater, int(10)),
+        ])];
+
+        assert_eq!(
+            check(&hypotheses, &atom(var(0), Relation::NotEq, int(5))),
+            Verdict::Proven,
+        );
+        assert_eq!(
+            check(&hypotheses, &atom(var(0), Relation::Eq, int(5))),
+            Verdict::Refuted,
+        );
+        assert_eq!(
+            check(&hypotheses, &atom(var(0), Relation::Greater, int(0))),
+            Verdict::Unknown,
+        );
+    }
+
+    #[test]
+    fn negated_conjunctions() {
+        // not (x > 0 and x < 10) means x is outside of 1..=9
+        let inside = Formula::And(vec![
+            atom(var(0), Relation::Greater, int(0)),
+            atom(var(0), Relation::Less, int(10)),
+        ]);
+
+        assert_eq!(
+            check(&[inside.not()], &atom(var(0), Relation::NotEq, int(5))),
+            Verdict::Proven,
+        );
+    }
+
+    #[test]
+    fn gcd_tightening() {
+        // 2x = 1 has a rational solution but no integer one
+        let two_x = var(0).scale(2).unwrap();
+        assert!(unsatisfiable(&atom(two_x.clone(), Relation::Eq, int(1))));
+        assert!(!unsatisfiable(&atom(two_x, Relation::Eq, int(2))));
+
+        // 2x + 1 <= 0 is x <= -1/2, which is x <= -1 over the integers
+        let tightened = tighten(var(0).scale(2).unwrap().add(&int(1)).unwrap());
+        assert_eq!(tightened, var(0).add(&int(1)).unwrap());
+    }
+
+    #[test]
+    fn overflowing_atoms_give_up() {
+        // `MAX < x` overflows when it's turned into `MAX - x + 1 <= 0`
+        let overflowing = atom(int(i128::MAX), Relation::Less, var(0));
+        assert!(overflowing.clone().dnf(false).is_none());
+
+        // Even a conjunction that's false regardless can't be decided
+        assert!(!unsatisfiable(&Formula::And(vec![
+            Formula::False,
+            overflowing
+        ])));
+    }
+
+    #[test]
+    fn overflowing_elimination_gives_up() {
+        // kx + y <= 0 and -kx - y + 1 <= 0 sum to 1 <= 0, but eliminating x first
+        // multiplies the two by k
+        let k = 1 << 70;
+        let kx = var(0).scale(k).unwrap();
+        let constraints = vec![
+            kx.add(&var(1)).unwrap(),
+            kx.add(&var(1))
+                .unwrap()
+                .scale(-1)
+                .unwrap()
+                .add(&int(1))
+                .unwrap(),
+            var(1).sub(&int(5)).unwrap(),
+        ];
+
+        assert!(!infeasible(constraints));
+    }
+
+    #[test]
+    fn too_many_cubes_give_up() {
+        // Every `!=` doubles the number of cubes
+        let mut hypotheses: Vec<Formula> = (1..=9)
+            .map(|var| atom(Linear::var(var), Relation::NotEq, int(0)))
+            .collect();
+        hypotheses.push(atom(var(0), Relation::Greater, int(0)));
+
+        let goal = atom(var(0), Relation::GreaterEq, int(1));
+        assert_eq!(check(&hypotheses[8..], &goal), Verdict::Proven);
+        assert_eq!(check(&hypotheses, &goal), Verdict::Unknown);
+    }
+
+    #[test]
+    fn too_many_constraints_give_up() {
+        // x >= 1000 alongside x <= i for every i
+        let bounds = |count: i128| {
+            let mut constraints: Vec<Linear> = (0..count)
+                .map(|bound| var(0).sub(&int(bound)).unwrap())
+                .collect();
+            constraints.push(int(1000).sub(&var(0)).unwrap());
+            constraints
+        };
+
+        assert!(infeasible(bounds(MAX_CONSTRAINTS as i128 - 1)));
+        assert!(!infeasible(bounds(MAX_CONSTRAINTS as i128 + 1)));
+    }
+}
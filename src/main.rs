@@ -1,183 +1,944 @@
 #![feature(once_cell, min_const_generics)]
 
 mod ast;
-
-use lalrpop_util::lalrpop_mod;
+mod fmt;
+mod ident;
+mod layout;
+mod lexer;
+mod lia;
+mod pretty;
+mod refine;
+mod syntax;
+
+use ast::visit::Visitor;
+use lalrpop_util::{lalrpop_mod, ParseError};
 use std::{cell::RefCell, marker::PhantomData, mem, rc::Rc};
 
-lalrpop_mod!(grammar);
+lalrpop_mod!(#[allow(clippy::all)] grammar);
+
+pub use ident::Ident;
 
-/*
-use std::{
-    fmt::{Debug, Formatter, Result as FmtResult},
-    lazy::SyncLazy,
-    ops::Deref,
-    sync::RwLock,
+use differential_datalog::{
+    ddval::{DDValConvert, DDValue},
+    program::{RelId, Update},
+    record::Record,
+    DDlog, DeltaMap,
 };
-use fxhash::FxBuildHasher;
-use lasso::{Rodeo, Spur};
+use typecheck_ddlog::api::HDDlog;
+use types::*;
+use value::{relid2name, Relations, Value};
 
-static INTERNER: SyncLazy<RwLock<Rodeo<Spur, FxBuildHasher>>> =
-    SyncLazy::new(|| RwLock::new(Rodeo::with_hasher(FxBuildHasher::default())));
+const EXAMPLE: &str = "\
+    fn main =\n    \
+        let x := 5;\n    \
+        add <| x 5;\n\
+";
 
-#[derive(Copy, Clone, PartialEq, Eq, Hash)]
-#[repr(transparent)]
-pub struct Ident(Spur);
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("fmt") {
+        return fmt_command(&args[2..]);
+    }
 
-impl Ident {
-    fn as_str<'a>(&'a self) -> &'a str {
-        // Safety: *Technically* not safe, just don't hold an outstanding reference
-        //         and clear the interner or you'll have a bad time
-        unsafe {
-            std::mem::transmute::<&str, &'a str>(INTERNER.read().unwrap().resolve(&self.0).as_ref())
+    // `--debug` dumps the AST along with every change Datalog makes to its relations
+    let debug = args[1..].iter().any(|arg| arg == "--debug");
+    let (file, source) = match args[1..].iter().find(|arg| *arg != "--debug") {
+        Some(path) => {
+            let source = std::fs::read_to_string(path)?;
+            (path.clone(), source)
         }
+        None => ("<example>".to_owned(), EXAMPLE.to_owned()),
+    };
+
+    for diagnostic in check(&file, &source, debug)? {
+        println!("{}", diagnostic);
     }
+
+    Ok(())
 }
 
-impl Deref for Ident {
-    type Target = str;
+/// Checks a file, returning the errors and warnings for everything wrong with it. Errors
+/// that come from parsing it are printed as they're found instead
+fn check(file: &str, source: &str, debug: bool) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let items = match parse(file, source, &mut Vec::new()).0 {
+        Some(tree) => syntax::lower(&tree),
+        None => return Err(format!("could not parse {}", file).into()),
+    };
+    if debug {
+        println!("{:#?}", items);
+    }
+
+    let obligations = refine::check(source, &items);
+
+    let mut ddlog = Datalog::new(debug)?;
+
+    ddlog.transaction(move |trans| {
+        let toplevel_scope = trans.scope();
+        datalog_builtins(&toplevel_scope);
+
+        let mut lowering = Lowering::new(toplevel_scope, 0);
+        for item in items.iter() {
+            lowering.visit_item(item);
+        }
+
+        Ok(())
+    })?;
+
+    let mut diagnostics = Vec::new();
+    for var in ddlog.out_of_scope_vars.iter() {
+        diagnostics.push(format!(
+            "{}: error: Could not find a variable named {:?}",
+            location(file, source, &var.span),
+            var.variable,
+        ));
+    }
+
+    for import in ddlog.unresolved_imports.iter() {
+        let path: Vec<&str> = import.path.iter().map(|segment| segment.as_str()).collect();
+
+        diagnostics.push(format!(
+            "{}: error: Could not resolve the import {:?}",
+            location(file, source, &import.span),
+            path.join("."),
+        ));
+    }
+
+    for func in ddlog.nonexistant_functions.iter() {
+        diagnostics.push(format!(
+            "{}: error: Could not find a function named {:?}",
+            location(file, source, &func.span),
+            func.name,
+        ));
+    }
+
+    for arm in ddlog.mismatched_arms.iter() {
+        diagnostics.push(format!(
+            "{}: error: Match arms have incompatible types, expected {} but found {}",
+            location(file, source, &arm.span),
+            display_type(&arm.expected),
+            display_type(&arm.found),
+        ));
+    }
+
+    for pattern in ddlog.incompatible_patterns.iter() {
+        diagnostics.push(format!(
+            "{}: error: A pattern of type {} can't match a value of type {}",
+            location(file, source, &pattern.span),
+            display_type(&pattern.pattern),
+            display_type(&pattern.value),
+        ));
+    }
+
+    for elem in ddlog.mismatched_list_elems.iter() {
+        diagnostics.push(format!(
+            "{}: error: List elements have incompatible types, expected {} but found {}",
+            location(file, source, &elem.span),
+            display_type(&elem.expected),
+            display_type(&elem.found),
+        ));
+    }
+
+    for arg in ddlog.argument_mismatches.iter() {
+        diagnostics.push(format!(
+            "{}: error: Arguments must match their parameter, expected {} but found {}",
+            location(file, source, &arg.span),
+            display_type(&arg.expected),
+            display_type(&arg.found),
+        ));
+    }
+
+    for arity in ddlog.arity_mismatches.iter() {
+        diagnostics.push(format!(
+            "{}: error: Function takes {} argument(s) but was given {}",
+            location(file, source, &arity.span),
+            arity.expected,
+            arity.found,
+        ));
+    }
+
+    for branch in ddlog.mismatched_branches.iter() {
+        diagnostics.push(format!(
+            "{}: error: If branches have incompatible types, expected {} but found {}",
+            location(file, source, &branch.span),
+            display_type(&branch.expected),
+            display_type(&branch.found),
+        ));
+    }
+
+    for contract in ddlog.non_bool_contracts.iter() {
+        diagnostics.push(format!(
+            "{}: error: Contracts must be of type Bool, found {}",
+            location(file, source, &contract.span),
+            display_type(&contract.ty),
+        ));
+    }
+
+    for cond in ddlog.non_bool_conditions.iter() {
+        diagnostics.push(format!(
+            "{}: error: Conditions must be of type Bool, found {}",
+            location(file, source, &cond.span),
+            display_type(&cond.ty),
+        ));
+    }
+
+    for operands in ddlog.mismatched_operands.iter() {
+        diagnostics.push(format!(
+            "{}: error: Can't compare a value of type {} with a value of type {}",
+            location(file, source, &operands.span),
+            display_type(&operands.lhs),
+            display_type(&operands.rhs),
+        ));
+    }
+
+    for negation in ddlog.non_bool_negations.iter() {
+        diagnostics.push(format!(
+            "{}: error: Only values of type Bool can be negated, found {}",
+            location(file, source, &negation.span),
+            display_type(&negation.ty),
+        ));
+    }
+
+    for ascription in ddlog.ascription_mismatches.iter() {
+        diagnostics.push(format!(
+            "{}: error: Expression was ascribed the type {} but has the type {}",
+            location(file, source, &ascription.span),
+            display_type(&ascription.expected),
+            display_type(&ascription.found),
+        ));
+    }
+
+    for refinement in ddlog.non_bool_refinements.iter() {
+        diagnostics.push(format!(
+            "{}: error: Refinements must be of type Bool, found {}",
+            location(file, source, &refinement.span),
+            display_type(&refinement.ty),
+        ));
+    }
+
+    for ty in ddlog.unknown_types.iter() {
+        let path: Vec<&str> = ty.path.iter().map(|segment| segment.as_str()).collect();
+
+        diagnostics.push(format!(
+            "{}: error: Could not find a type named {:?}",
+            location(file, source, &ty.span),
+            path.join("."),
+        ));
+    }
+
+    for field in ddlog.no_such_fields.iter() {
+        diagnostics.push(format!(
+            "{}: error: The type {} has no field named {:?}",
+            location(file, source, &field.span),
+            display_type(&field.ty),
+            field.field,
+        ));
+    }
+
+    for field in ddlog.missing_fields.iter() {
+        diagnostics.push(format!(
+            "{}: error: Missing the field {:?} of {}",
+            location(file, source, &field.span),
+            field.field,
+            field.ty.as_str(),
+        ));
+    }
+
+    for field in ddlog.field_mismatches.iter() {
+        diagnostics.push(format!(
+            "{}: error: The field {:?} has the type {} but was given a value of type {}",
+            location(file, source, &field.span),
+            field.field,
+            display_type(&field.expected),
+            display_type(&field.found),
+        ));
+    }
+
+    for ctor in ddlog.unknown_constructors.iter() {
+        diagnostics.push(format!(
+            "{}: error: Could not find a constructor named {:?}",
+            location(file, source, &ctor.span),
+            ctor.ctor,
+        ));
+    }
+
+    for ctor in ddlog.constructor_arities.iter() {
+        diagnostics.push(format!(
+            "{}: error: The constructor {:?} takes {} fields but was given {}",
+            location(file, source, &ctor.span),
+            ctor.ctor,
+            ctor.expected,
+            ctor.found,
+        ));
+    }
+
+    for ctor in ddlog.missing_constructors.iter() {
+        diagnostics.push(format!(
+            "{}: error: Match is not exhaustive, the constructor {:?} is not covered",
+            location(file, source, &ctor.span),
+            ctor.ctor,
+        ));
+    }
+
+    for catch_all in ddlog.missing_catch_alls.iter() {
+        diagnostics.push(format!(
+            "{}: error: Match is not exhaustive, it needs an arm that matches any {}",
+            location(file, source, &catch_all.span),
+            display_type(&catch_all.ty),
+        ));
+    }
+
+    for arm in ddlog.redundant_arms.iter() {
+        diagnostics.push(format!(
+            "{}: warning: This arm is unreachable, earlier arms match everything it does",
+            location(file, source, &arm.span),
+        ));
+    }
 
-    fn deref(&self) -> &Self::Target {
-        self.as_str()
+    for binding in ddlog.refutable_lets.iter() {
+        diagnostics.push(format!(
+            "{}: error: The pattern of a let binding has to match every value, use a match instead",
+            location(file, source, &binding.span),
+        ));
     }
+
+    for obligation in obligations.iter() {
+        let span = ddlog_span(obligation.span);
+
+        diagnostics.push(match obligation.verdict {
+            lia::Verdict::Refuted => format!(
+                "{}: error: The refinement `{}` never holds here",
+                location(file, source, &span),
+                obligation.constraint,
+            ),
+            _ => format!(
+                "{}: error: Could not prove that the refinement `{}` holds",
+                location(file, source, &span),
+                obligation.constraint,
+            ),
+        });
+    }
+
+    for expr in ddlog.uninfered_exprs.iter() {
+        diagnostics.push(format!(
+            "{}: error: Could not infer the type of this expression",
+            location(file, source, &expr.span),
+        ));
+    }
+
+    Ok(diagnostics)
 }
 
-impl Debug for Ident {
-    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        Debug::fmt(self.as_str(), f)
+/// Parses a file and reports every error within it, returning its syntax tree if parsing could
+/// recover from all of them along with the number of errors. Comments are collected into `comments`
+fn parse<'a>(
+    file: &str,
+    source: &'a str,
+    comments: &mut Vec<lexer::Comment<'a>>,
+) -> (Option<syntax::nodes::SourceFile>, usize) {
+    let (mut lex_errors, mut syntax_errors) = (Vec::new(), Vec::new());
+    let tokens = lexer::Lexer::new(source, &mut lex_errors).with_comments(comments);
+    let parsed =
+        grammar::ItemsParser::new().parse(&mut syntax_errors, layout::Layout::new(source, tokens));
+
+    for error in lex_errors.iter() {
+        println!(
+            "{}: error: {}",
+            location(file, source, &ddlog_span(error.span)),
+            error,
+        );
+    }
+
+    for recovered in syntax_errors.iter() {
+        let (span, message) = syntax_error(&recovered.error);
+        println!(
+            "{}: error: {}",
+            location(file, source, &ddlog_span(span)),
+            message,
+        );
+    }
+
+    let errors = lex_errors.len() + syntax_errors.len();
+    match parsed {
+        Ok(items) => (Some(syntax::build(source, items)), errors),
+        Err(error) => {
+            let (span, message) = syntax_error(&error);
+            println!(
+                "{}: error: {}",
+                location(file, source, &ddlog_span(span)),
+                message,
+            );
+
+            (None, errors + 1)
+        }
     }
 }
-*/
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-#[repr(transparent)]
-pub struct Ident(String);
+/// `fmt [--check] [--width <columns>] <files>...`
+///
+/// Rewrites each file in its canonical format, or with `--check` only lists the files
+/// that aren't formatted and fails if there are any
+fn fmt_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let (mut check, mut width, mut files) = (false, fmt::DEFAULT_WIDTH, Vec::new());
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check" => check = true,
+            "--width" => width = args.next().ok_or("--width needs a value")?.parse()?,
+            file => files.push(file),
+        }
+    }
 
-use differential_datalog::{
-    ddval::{DDValConvert, DDValue},
-    program::{RelId, Update},
-    record::Record,
-    DDlog, DeltaMap,
-};
-use typecheck_ddlog::api::HDDlog;
-use types::*;
-use value::{relid2name, Relations, Value};
+    let (mut unformatted, mut failed) = (0, 0);
+    for file in files {
+        let source = std::fs::read_to_string(file)?;
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let source = "\
-        fn main =\n    \
-            let x := 5;\n    \
-            add <| x 5;\n\
-    ";
+        let mut comments = Vec::new();
+        let items = match parse(file, &source, &mut comments) {
+            (Some(tree), 0) => syntax::lower(&tree),
+            _ => {
+                failed += 1;
+                continue;
+            }
+        };
 
-    let items = grammar::ItemsParser::new().parse(source)?;
-    println!("{:#?}", items);
+        let formatted = fmt::format(&source, &items, &comments, width);
+
+        // Never write out something that no longer parses
+        let (mut lex_errors, mut syntax_errors) = (Vec::new(), Vec::new());
+        let tokens = lexer::Lexer::new(&formatted, &mut lex_errors);
+        let reparsed = grammar::ItemsParser::new()
+            .parse(&mut syntax_errors, layout::Layout::new(&formatted, tokens));
+        if reparsed.is_err() || !lex_errors.is_empty() || !syntax_errors.is_empty() {
+            println!("{}: error: Formatting produced invalid code", file);
+            failed += 1;
+            continue;
+        }
 
-    let mut ddlog = Datalog::new()?;
+        if formatted != source {
+            if check {
+                println!("{}: not formatted", file);
+                unformatted += 1;
+            } else {
+                std::fs::write(file, formatted)?;
+            }
+        }
+    }
 
-    ddlog.transaction(move |trans| {
-        let toplevel_scope = trans.scope();
+    if failed != 0 {
+        Err(format!("could not format {} file(s)", failed).into())
+    } else if unformatted != 0 {
+        Err(format!("{} file(s) need formatting", unformatted).into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Declares the functions every program can use without defining them, they only
+/// have signatures since their bodies are provided by the runtime
+fn datalog_builtins(scope: &Scope<'_>) {
+    let var = |name: &str| Type::TVar {
+        name: Ident::new(name).to_istring(),
+    };
+    let list = |elem: Type| Type::List {
+        elem: ddlog_std::ref_new(&elem),
+    };
+    let func = |args: Vec<Type>, ret: Type| Type::Func {
+        args: args.into(),
+        ret: ddlog_std::ref_new(&ret),
+    };
+
+    let builtins = vec![
+        // map f: fn('a) -> 'b xs: List 'a -> List 'b
+        (
+            "map",
+            vec![func(vec![var("a")], var("b")), list(var("a"))],
+            list(var("b")),
+        ),
+        // fold f: fn('b, 'a) -> 'b init: 'b xs: List 'a -> 'b
+        (
+            "fold",
+            vec![
+                func(vec![var("b"), var("a")], var("b")),
+                var("b"),
+                list(var("a")),
+            ],
+            var("b"),
+        ),
+        // len xs: List 'a -> Int
+        ("len", vec![list(var("a"))], Type::Int),
+        // cons x: 'a xs: List 'a -> List 'a
+        ("cons", vec![var("a"), list(var("a"))], list(var("a"))),
+    ];
+
+    for (name, args, ret) in builtins {
+        let function_id = scope.next_func_id();
+        scope.push_function(Function {
+            name: Ident::new(name).to_istring(),
+            id: function_id,
+            ret: Some(ret).into(),
+            scope: scope.id(),
+        });
+
+        for (idx, ty) in args.into_iter().enumerate() {
+            scope.push_func_arg(FuncArg {
+                func: function_id,
+                idx: idx as u64,
+                ty,
+            });
+        }
+    }
+}
 
-        for item in items {
-            match item {
-                ast::Item::Func(func) => {
-                    let function_id = toplevel_scope.next_func_id();
-                    toplevel_scope.push_function(Function {
-                        name: internment::intern(&func.name.0),
-                        id: function_id,
-                        ret: func.ret.map(ddlog_type).into(),
-                        scope: toplevel_scope.id(),
+/// Lowers items and the expressions within them into Datalog
+struct Lowering<'ddlog> {
+    /// The scope new items and expressions are declared in, each expression in a
+    /// function's body is declared within the one before it so that it sees its bindings
+    scope: Scope<'ddlog>,
+    function_id: u32,
+    /// The expression being lowered, or the one that was lowered last
+    expr: u32,
+}
+
+impl<'ddlog> Lowering<'ddlog> {
+    fn new(scope: Scope<'ddlog>, function_id: u32) -> Self {
+        Self {
+            scope,
+            function_id,
+            expr: 0,
+        }
+    }
+
+    /// Lowers `expr` within `scope`, returning its id
+    fn child(&mut self, scope: Scope<'ddlog>, expr: &ast::Expr) -> u32 {
+        self.scope = scope;
+        self.visit_expr(expr);
+        self.expr
+    }
+}
+
+impl<'ast> Visitor<'ast> for Lowering<'_> {
+    fn visit_func_def(&mut self, func: &'ast ast::FuncDef) {
+        let function_id = self.scope.next_func_id();
+        self.scope.push_function(Function {
+            name: func.name.to_istring(),
+            id: function_id,
+            ret: func.ret.clone().map(ddlog_type).into(),
+            scope: self.scope.id(),
+        });
+
+        let function_scope = self.scope.scope();
+        if let Some(ret) = func.ret.as_ref() {
+            datalog_type_refs(&function_scope, ret);
+            datalog_refinements(&function_scope, function_id, ret);
+        }
+
+        for (idx, (pat, ty)) in func.params.iter().enumerate() {
+            datalog_type_refs(&function_scope, ty);
+            datalog_refinements(&function_scope, function_id, ty);
+            function_scope.push_func_arg(FuncArg {
+                func: function_id,
+                idx: idx as u64,
+                ty: ddlog_type(ty.clone()),
+            });
+
+            function_scope.push_func_param(FuncParam {
+                func: function_id,
+                idx: idx as u64,
+                pattern: ddlog_pattern(pat.clone()),
+                scope: function_scope.id(),
+                span: ddlog_span(pat.span),
+            });
+        }
+
+        let parent = mem::replace(&mut self.scope, function_scope);
+        self.function_id = function_id;
+        for expr in func.body.iter() {
+            self.visit_expr(expr);
+        }
+        self.scope = parent;
+    }
+
+    fn visit_module(&mut self, module: &'ast ast::Module) {
+        let module_scope = self.scope.scope();
+        self.scope.push_module(Module {
+            name: module.name.to_istring(),
+            scope: module_scope.id(),
+            parent: self.scope.id(),
+        });
+
+        let parent = mem::replace(&mut self.scope, module_scope);
+        ast::visit::walk_module(self, module);
+        self.scope = parent;
+    }
+
+    fn visit_type_def(&mut self, def: &'ast ast::TypeDef) {
+        let scope = &self.scope;
+        scope.push_type_decl(TypeDecl {
+            name: def.name.to_istring(),
+            scope: scope.id(),
+            span: ddlog_span(def.span),
+        });
+
+        match &def.kind {
+            // Refinements on fields go unchecked since their predicates
+            // aren't part of any function
+            ast::TypeDefKind::Record(fields) => {
+                for field in fields {
+                    datalog_type_refs(scope, &field.ty);
+                    scope.push_type_field(TypeField {
+                        ty: def.name.to_istring(),
+                        field: field.name.to_istring(),
+                        field_ty: ddlog_type(field.ty.clone()),
                     });
+                }
+            }
 
-                    let function_scope = toplevel_scope.scope();
-
-                    for (pat, ty) in func.params {
-                        if let ast::Pattern::Ident(name) = pat {
-                            function_scope.push_func_arg(FuncArg {
-                                func: function_id,
-                                name: internment::intern(&name.0),
-                                ty: ddlog_type(ty),
-                            });
-                        } else {
-                            todo!()
-                        }
-                    }
+            ast::TypeDefKind::Variants(variants) => {
+                for variant in variants {
+                    scope.push_variant(Variant {
+                        ty: def.name.to_istring(),
+                        ctor: variant.name.to_istring(),
+                        arity: variant.fields.len() as u64,
+                        scope: scope.id(),
+                    });
 
-                    let mut last_scope = function_scope;
-                    for expr in func.body {
-                        last_scope = datalog_expression(last_scope, function_id, expr).1;
+                    for (idx, field) in variant.fields.iter().enumerate() {
+                        datalog_type_refs(scope, field);
+                        scope.push_variant_field(VariantField {
+                            ctor: variant.name.to_istring(),
+                            idx: idx as u64,
+                            field_ty: ddlog_type(field.clone()),
+                        });
                     }
                 }
+            }
+        }
+    }
 
-                ast::Item::Module(_) => todo!(),
+    fn visit_usage(&mut self, usage: &'ast ast::Usage) {
+        let path: Vec<_> = usage
+            .path
+            .segments
+            .iter()
+            .map(|segment| segment.to_istring())
+            .collect();
+
+        self.scope.push_import(Import {
+            id: self.scope.next_import_id(),
+            path: path.into(),
+            scope: self.scope.id(),
+            span: ddlog_span(usage.span),
+        });
+    }
+
+    /// Declares the expression in a new scope and lowers whatever is within it,
+    /// leaving the lowering within that scope
+    fn visit_expr(&mut self, expr: &'ast ast::Expr) {
+        let expr_scope = self.scope.scope();
+        let expression_id = expr_scope.next_expr_id();
+
+        expr_scope.push_expression(Expression {
+            id: expression_id,
+            func: self.function_id,
+            kind: expr_kind(&expr.kind),
+            scope: expr_scope.id(),
+        });
+        expr_scope.push_expr_span(ExprSpan {
+            expr: expression_id,
+            span: ddlog_span(expr.span),
+        });
+
+        self.scope = expr_scope.clone();
+        self.expr = expression_id;
+
+        // These have no visitor methods of their own
+        match &expr.kind {
+            ast::ExprKind::Paren(inner) => {
+                let inner = self.child(expr_scope.clone(), inner);
+                expr_scope.push_parenthesized(Parenthesized {
+                    expr: expression_id,
+                    inner,
+                });
             }
+
+            ast::ExprKind::Tuple(elems) => {
+                expr_scope.push_tuple_arity(TupleArity {
+                    expr: expression_id,
+                    arity: elems.len() as u64,
+                });
+
+                for (idx, elem) in elems.iter().enumerate() {
+                    let elem = self.child(expr_scope.clone(), elem);
+                    expr_scope.push_tuple_elem(TupleElem {
+                        expr: expression_id,
+                        idx: idx as u64,
+                        elem,
+                    });
+                }
+            }
+
+            ast::ExprKind::List(elems) => {
+                for (idx, elem) in elems.iter().enumerate() {
+                    let elem = self.child(expr_scope.clone(), elem);
+                    expr_scope.push_list_elem(ListElem {
+                        expr: expression_id,
+                        idx: idx as u64,
+                        elem,
+                    });
+                }
+            }
+
+            // The record's name is referenced from the whole expression
+            ast::ExprKind::Record(record) => {
+                expr_scope.push_type_ref(TypeRef {
+                    path: vec![record.name.to_istring()].into(),
+                    scope: expr_scope.id(),
+                    span: ddlog_span(expr.span),
+                });
+                self.visit_record(record);
+            }
+
+            _ => ast::visit::walk_expr(self, expr),
         }
 
-        Ok(())
-    })?;
+        self.scope = expr_scope;
+        self.expr = expression_id;
+    }
 
-    for var in ddlog.out_of_scope_vars.iter() {
-        println!("error: Could not find a variable named {:?}", var.variable);
+    fn visit_let(&mut self, binding: &'ast ast::Let) {
+        let (scope, expr) = (self.scope.clone(), self.expr);
+        let val = self.child(scope.clone(), &binding.value);
+
+        scope.push_var_decl(VarDecl {
+            expr,
+            pattern: ddlog_pattern(binding.binding.clone()),
+            val,
+        });
     }
 
-    Ok(())
-}
+    fn visit_literal(&mut self, lit: &'ast ast::Literal) {
+        self.scope.push_literal(Literal {
+            expr: self.expr,
+            lit: ddlog_literal(lit.clone()),
+        });
+    }
 
-fn datalog_expression(scope: Scope<'_>, function_id: u32, expr: ast::Expr) -> (u32, Scope<'_>) {
-    let expr_scope = scope.scope();
-    let expression_id = expr_scope.next_expr_id();
+    fn visit_app(&mut self, app: &'ast ast::App) {
+        let (scope, expr) = (self.scope.clone(), self.expr);
 
-    expr_scope.push_expression(Expression {
-        id: expression_id,
-        func: function_id,
-        kind: expr_kind(&expr),
-        scope: expr_scope.id(),
-    });
+        let func = self.child(scope.clone(), &app.func);
+        scope.push_app(Application { expr, func });
 
-    match expr {
-        ast::Expr::Let(binding) => {
-            // TODO: Decl rhs
-            let val = datalog_expression(expr_scope.clone(), function_id, binding.value).0;
-            expr_scope.push_var_decl(VarDecl {
-                expr: expression_id,
-                name: if let ast::Pattern::Ident(ident) = binding.binding {
-                    internment::intern(&ident.0)
-                } else {
-                    todo!()
-                },
-                val,
+        for (idx, arg) in app.args.iter().enumerate() {
+            let arg = self.child(scope.clone(), arg);
+            scope.push_app_arg(ApplicationArg {
+                expr,
+                idx: idx as u64,
+                arg,
             });
         }
+    }
+
+    fn visit_match(&mut self, match_expr: &'ast ast::Match) {
+        let (scope, expr) = (self.scope.clone(), self.expr);
+
+        let scrutinee = self.child(scope.clone(), &match_expr.scrutinee);
+        scope.push_match_scrutinee(MatchScrutinee { expr, scrutinee });
+
+        for arm in match_expr.arms.iter() {
+            let arm_scope = scope.scope();
+            let body = self.child(arm_scope.clone(), &arm.body);
 
-        ast::Expr::Literal(lit) => {
-            expr_scope.push_literal(Literal {
-                expr: expression_id,
-                lit: ddlog_literal(lit),
+            arm_scope.push_match_arm(MatchArm {
+                expr,
+                scope: arm_scope.id(),
+                pattern: ddlog_pattern(arm.pattern.clone()),
+                body,
+                span: ddlog_span(arm.span),
             });
         }
+    }
+
+    fn visit_if(&mut self, if_expr: &'ast ast::If) {
+        let (scope, expr) = (self.scope.clone(), self.expr);
+
+        let cond = self.child(scope.clone(), &if_expr.cond);
+        let then_branch = self.child(scope.scope(), &if_expr.then_branch);
+        let else_branch = self.child(scope.scope(), &if_expr.else_branch);
+
+        scope.push_if_expr(IfExpr {
+            expr,
+            cond,
+            then_branch,
+            else_branch,
+        });
+    }
 
-        ast::Expr::App(app) => {
-            let func = datalog_expression(expr_scope.clone(), function_id, app.func).0;
-            expr_scope.push_app(Application {
-                expr: expression_id,
-                func,
+    fn visit_ensure(&mut self, ensure: &'ast ast::Ensure) {
+        let (scope, expr) = (self.scope.clone(), self.expr);
+
+        let contract = self.child(scope.clone(), &ensure.contract);
+        scope.push_ensure(EnsureContract { expr, contract });
+    }
+
+    fn visit_binary(&mut self, binary: &'ast ast::Binary) {
+        let (scope, expr) = (self.scope.clone(), self.expr);
+
+        let lhs = self.child(scope.clone(), &binary.lhs);
+        let rhs = self.child(scope.clone(), &binary.rhs);
+        scope.push_binary(BinaryExpr {
+            expr,
+            op: ddlog_binary_op(binary.op),
+            lhs,
+            rhs,
+        });
+    }
+
+    fn visit_unary(&mut self, unary: &'ast ast::Unary) {
+        let (scope, expr) = (self.scope.clone(), self.expr);
+
+        let operand = self.child(scope.clone(), &unary.expr);
+        scope.push_unary(UnaryExpr {
+            expr,
+            op: ddlog_unary_op(unary.op),
+            operand,
+        });
+    }
+
+    fn visit_ascription(&mut self, ascription: &'ast ast::Ascription) {
+        let (scope, expr) = (self.scope.clone(), self.expr);
+
+        datalog_type_refs(&scope, &ascription.ty);
+        datalog_refinements(&scope, self.function_id, &ascription.ty);
+        let inner = self.child(scope.clone(), &ascription.expr);
+        scope.push_ascription(Ascription {
+            expr,
+            inner,
+            ty: ddlog_type(ascription.ty.clone()),
+        });
+    }
+
+    fn visit_lambda(&mut self, lambda: &'ast ast::Lambda) {
+        let (scope, expr) = (self.scope.clone(), self.expr);
+
+        // The parameters are only visible within the lambda, any other variable
+        // its body uses is captured from the scopes around it
+        let lambda_scope = scope.scope();
+        for (idx, (pat, ty)) in lambda.params.iter().enumerate() {
+            datalog_type_refs(&lambda_scope, ty);
+            datalog_refinements(&lambda_scope, self.function_id, ty);
+
+            lambda_scope.push_lambda_param(LambdaParam {
+                expr,
+                idx: idx as u64,
+                pattern: ddlog_pattern(pat.clone()),
+                ty: ddlog_type(ty.clone()),
+                scope: lambda_scope.id(),
+                span: ddlog_span(pat.span),
             });
+        }
 
-            for arg in app.args {
-                let arg = datalog_expression(expr_scope.clone(), function_id, arg).0;
-                expr_scope.push_app_arg(ApplicationArg {
-                    expr: expression_id,
-                    arg,
+        let body = self.child(lambda_scope, &lambda.body);
+        scope.push_lambda_body(LambdaBody { expr, body });
+    }
+
+    fn visit_record(&mut self, record: &'ast ast::Record) {
+        let (scope, expr) = (self.scope.clone(), self.expr);
+
+        scope.push_record_expr(RecordExpr {
+            expr,
+            ty: record.name.to_istring(),
+        });
+
+        for field in record.fields.iter() {
+            let value = self.child(scope.clone(), &field.value);
+            scope.push_record_expr_field(RecordExprField {
+                expr,
+                field: field.name.to_istring(),
+                value,
+                span: ddlog_span(field.span),
+            });
+        }
+    }
+
+    fn visit_field_access(&mut self, access: &'ast ast::FieldAccess) {
+        let (scope, expr) = (self.scope.clone(), self.expr);
+
+        let record = self.child(scope.clone(), &access.record);
+        scope.push_field_access(FieldAccess {
+            expr,
+            record,
+            field: access.field.to_istring(),
+        });
+    }
+
+    // Patterns are lowered whole along with whatever binds them
+    fn visit_pattern(&mut self, _pattern: &'ast ast::Pattern) {}
+}
+
+/// Records every named type within a type so that each can be resolved from `scope`
+fn datalog_type_refs(scope: &Scope<'_>, ty: &ast::Type) {
+    struct TypeRefs<'a, 'ddlog> {
+        scope: &'a Scope<'ddlog>,
+    }
+
+    impl<'ast> Visitor<'ast> for TypeRefs<'_, '_> {
+        fn visit_type(&mut self, ty: &'ast ast::Type) {
+            if let ast::TypeKind::Path(path) = &ty.kind {
+                let path: Vec<_> = path
+                    .segments
+                    .iter()
+                    .map(|segment| segment.to_istring())
+                    .collect();
+
+                self.scope.push_type_ref(TypeRef {
+                    path: path.into(),
+                    scope: self.scope.id(),
+                    span: ddlog_span(ty.span),
                 });
             }
+
+            ast::visit::walk_type(self, ty);
         }
 
-        ast::Expr::Var(_) => {}
+        // Predicates are lowered as expressions, which takes care of the types within them
+        fn visit_refinement(&mut self, refinement: &'ast ast::Refinement) {
+            self.visit_type(&refinement.ty);
+        }
+    }
+
+    TypeRefs { scope }.visit_type(ty);
+}
 
-        _ => todo!(),
+/// Lowers the predicates of every refinement within a type, each in a new
+/// scope where its binder is defined
+fn datalog_refinements(scope: &Scope<'_>, function_id: u32, ty: &ast::Type) {
+    struct Refinements<'a, 'ddlog> {
+        scope: &'a Scope<'ddlog>,
+        function_id: u32,
     }
 
-    (expression_id, expr_scope)
+    impl<'ast> Visitor<'ast> for Refinements<'_, '_> {
+        // The predicate is lowered as an expression, which takes care of any refinements within it
+        fn visit_refinement(&mut self, refinement: &'ast ast::Refinement) {
+            self.visit_type(&refinement.ty);
+
+            let refinement_scope = self.scope.scope();
+            let mut lowering = Lowering::new(refinement_scope.clone(), self.function_id);
+            lowering.visit_expr(&refinement.predicate);
+            let predicate = lowering.expr;
+
+            refinement_scope.push_refinement(Refinement {
+                scope: refinement_scope.id(),
+                binder: refinement.binder.to_istring(),
+                ty: ddlog_type(refinement.ty.clone()),
+                predicate,
+            });
+        }
+    }
+
+    Refinements { scope, function_id }.visit_type(ty);
 }
 
 type DdlogResult<T> = Result<T, String>;
@@ -185,10 +946,37 @@ type DdlogResult<T> = Result<T, String>;
 struct Datalog {
     datalog: Rc<RefCell<DatalogInner>>,
     out_of_scope_vars: Vec<OutOfScopeVar>,
+    unresolved_imports: Vec<UnresolvedImport>,
+    nonexistant_functions: Vec<NonexistantFunction>,
+    mismatched_arms: Vec<MismatchedArms>,
+    mismatched_branches: Vec<MismatchedBranches>,
+    incompatible_patterns: Vec<IncompatiblePattern>,
+    mismatched_list_elems: Vec<MismatchedListElem>,
+    argument_mismatches: Vec<ArgumentMismatch>,
+    arity_mismatches: Vec<ArityMismatch>,
+    non_bool_contracts: Vec<NonBoolContract>,
+    non_bool_conditions: Vec<NonBoolCondition>,
+    mismatched_operands: Vec<MismatchedOperands>,
+    non_bool_negations: Vec<NonBoolNegation>,
+    ascription_mismatches: Vec<AscriptionMismatch>,
+    non_bool_refinements: Vec<NonBoolRefinement>,
+    unknown_types: Vec<UnknownType>,
+    no_such_fields: Vec<NoSuchField>,
+    missing_fields: Vec<MissingField>,
+    field_mismatches: Vec<FieldMismatch>,
+    unknown_constructors: Vec<UnknownConstructor>,
+    constructor_arities: Vec<ConstructorArity>,
+    missing_constructors: Vec<MissingConstructor>,
+    missing_catch_alls: Vec<MissingCatchAll>,
+    redundant_arms: Vec<RedundantArm>,
+    refutable_lets: Vec<RefutableLet>,
+    uninfered_exprs: Vec<UninferedExpr>,
+    /// Whether to print out the changes of every transaction
+    debug: bool,
 }
 
 impl Datalog {
-    pub fn new() -> DdlogResult<Self> {
+    pub fn new(debug: bool) -> DdlogResult<Self> {
         let (hddlog, init_state) = HDDlog::run(2, false, |_: usize, _: &Record, _: isize| {})?;
         let mut this = Self {
             datalog: Rc::new(RefCell::new(DatalogInner {
@@ -197,8 +985,35 @@ impl Datalog {
                 scope_id: 0,
                 function_id: 0,
                 expression_id: 0,
+                import_id: 0,
             })),
             out_of_scope_vars: Vec::new(),
+            unresolved_imports: Vec::new(),
+            nonexistant_functions: Vec::new(),
+            mismatched_arms: Vec::new(),
+            mismatched_branches: Vec::new(),
+            incompatible_patterns: Vec::new(),
+            mismatched_list_elems: Vec::new(),
+            argument_mismatches: Vec::new(),
+            arity_mismatches: Vec::new(),
+            non_bool_contracts: Vec::new(),
+            non_bool_conditions: Vec::new(),
+            mismatched_operands: Vec::new(),
+            non_bool_negations: Vec::new(),
+            ascription_mismatches: Vec::new(),
+            non_bool_refinements: Vec::new(),
+            unknown_types: Vec::new(),
+            no_such_fields: Vec::new(),
+            missing_fields: Vec::new(),
+            field_mismatches: Vec::new(),
+            unknown_constructors: Vec::new(),
+            constructor_arities: Vec::new(),
+            missing_constructors: Vec::new(),
+            missing_catch_alls: Vec::new(),
+            redundant_arms: Vec::new(),
+            refutable_lets: Vec::new(),
+            uninfered_exprs: Vec::new(),
+            debug,
         };
         this.update(init_state);
 
@@ -212,30 +1027,196 @@ impl Datalog {
         let mut trans = DatalogTransaction::new(self.datalog.clone())?;
         transaction(&mut trans)?;
         let delta = trans.commit()?;
+        if self.debug {
+            println!("State after transaction");
+            dump_delta(&delta);
+        }
         self.update(delta);
 
         Ok(())
     }
 
     fn update(&mut self, mut delta: DeltaMap<DDValue>) {
-        let out_of_scope_vars = delta.clear_rel(Relations::OutOfScopeVar as RelId);
-        for (var, weight) in out_of_scope_vars {
-            let var = unsafe { Value::OutOfScopeVar::from_ddvalue(var).0 };
-
-            match weight {
-                1 => self.out_of_scope_vars.push(var),
-                -1 => {
-                    let idx = self
-                        .out_of_scope_vars
-                        .iter()
-                        .position(|v| v == &var)
-                        .unwrap();
-
-                    self.out_of_scope_vars.remove(idx);
-                }
+        apply_changes(
+            &mut self.out_of_scope_vars,
+            &mut delta,
+            Relations::OutOfScopeVar,
+            |var| unsafe { Value::OutOfScopeVar::from_ddvalue(var).0 },
+        );
+        apply_changes(
+            &mut self.unresolved_imports,
+            &mut delta,
+            Relations::UnresolvedImport,
+            |import| unsafe { Value::UnresolvedImport::from_ddvalue(import).0 },
+        );
+        apply_changes(
+            &mut self.nonexistant_functions,
+            &mut delta,
+            Relations::NonexistantFunction,
+            |func| unsafe { Value::NonexistantFunction::from_ddvalue(func).0 },
+        );
+        apply_changes(
+            &mut self.mismatched_arms,
+            &mut delta,
+            Relations::MismatchedArms,
+            |arm| unsafe { Value::MismatchedArms::from_ddvalue(arm).0 },
+        );
+        apply_changes(
+            &mut self.mismatched_branches,
+            &mut delta,
+            Relations::MismatchedBranches,
+            |branch| unsafe { Value::MismatchedBranches::from_ddvalue(branch).0 },
+        );
+        apply_changes(
+            &mut self.incompatible_patterns,
+            &mut delta,
+            Relations::IncompatiblePattern,
+            |pattern| unsafe { Value::IncompatiblePattern::from_ddvalue(pattern).0 },
+        );
+        apply_changes(
+            &mut self.mismatched_list_elems,
+            &mut delta,
+            Relations::MismatchedListElem,
+            |elem| unsafe { Value::MismatchedListElem::from_ddvalue(elem).0 },
+        );
+        apply_changes(
+            &mut self.argument_mismatches,
+            &mut delta,
+            Relations::ArgumentMismatch,
+            |arg| unsafe { Value::ArgumentMismatch::from_ddvalue(arg).0 },
+        );
+        apply_changes(
+            &mut self.arity_mismatches,
+            &mut delta,
+            Relations::ArityMismatch,
+            |arity| unsafe { Value::ArityMismatch::from_ddvalue(arity).0 },
+        );
+        apply_changes(
+            &mut self.non_bool_contracts,
+            &mut delta,
+            Relations::NonBoolContract,
+            |contract| unsafe { Value::NonBoolContract::from_ddvalue(contract).0 },
+        );
+        apply_changes(
+            &mut self.non_bool_conditions,
+            &mut delta,
+            Relations::NonBoolCondition,
+            |cond| unsafe { Value::NonBoolCondition::from_ddvalue(cond).0 },
+        );
+        apply_changes(
+            &mut self.mismatched_operands,
+            &mut delta,
+            Relations::MismatchedOperands,
+            |operands| unsafe { Value::MismatchedOperands::from_ddvalue(operands).0 },
+        );
+        apply_changes(
+            &mut self.non_bool_negations,
+            &mut delta,
+            Relations::NonBoolNegation,
+            |negation| unsafe { Value::NonBoolNegation::from_ddvalue(negation).0 },
+        );
+        apply_changes(
+            &mut self.ascription_mismatches,
+            &mut delta,
+            Relations::AscriptionMismatch,
+            |ascription| unsafe { Value::AscriptionMismatch::from_ddvalue(ascription).0 },
+        );
+        apply_changes(
+            &mut self.non_bool_refinements,
+            &mut delta,
+            Relations::NonBoolRefinement,
+            |refinement| unsafe { Value::NonBoolRefinement::from_ddvalue(refinement).0 },
+        );
+        apply_changes(
+            &mut self.unknown_types,
+            &mut delta,
+            Relations::UnknownType,
+            |ty| unsafe { Value::UnknownType::from_ddvalue(ty).0 },
+        );
+        apply_changes(
+            &mut self.no_such_fields,
+            &mut delta,
+            Relations::NoSuchField,
+            |field| unsafe { Value::NoSuchField::from_ddvalue(field).0 },
+        );
+        apply_changes(
+            &mut self.missing_fields,
+            &mut delta,
+            Relations::MissingField,
+            |field| unsafe { Value::MissingField::from_ddvalue(field).0 },
+        );
+        apply_changes(
+            &mut self.field_mismatches,
+            &mut delta,
+            Relations::FieldMismatch,
+            |field| unsafe { Value::FieldMismatch::from_ddvalue(field).0 },
+        );
+        apply_changes(
+            &mut self.unknown_constructors,
+            &mut delta,
+            Relations::UnknownConstructor,
+            |ctor| unsafe { Value::UnknownConstructor::from_ddvalue(ctor).0 },
+        );
+        apply_changes(
+            &mut self.constructor_arities,
+            &mut delta,
+            Relations::ConstructorArity,
+            |ctor| unsafe { Value::ConstructorArity::from_ddvalue(ctor).0 },
+        );
+        apply_changes(
+            &mut self.missing_constructors,
+            &mut delta,
+            Relations::MissingConstructor,
+            |ctor| unsafe { Value::MissingConstructor::from_ddvalue(ctor).0 },
+        );
+        apply_changes(
+            &mut self.missing_catch_alls,
+            &mut delta,
+            Relations::MissingCatchAll,
+            |catch_all| unsafe { Value::MissingCatchAll::from_ddvalue(catch_all).0 },
+        );
+        apply_changes(
+            &mut self.redundant_arms,
+            &mut delta,
+            Relations::RedundantArm,
+            |arm| unsafe { Value::RedundantArm::from_ddvalue(arm).0 },
+        );
+        apply_changes(
+            &mut self.refutable_lets,
+            &mut delta,
+            Relations::RefutableLet,
+            |binding| unsafe { Value::RefutableLet::from_ddvalue(binding).0 },
+        );
+        apply_changes(
+            &mut self.uninfered_exprs,
+            &mut delta,
+            Relations::UninferedExpr,
+            |expr| unsafe { Value::UninferedExpr::from_ddvalue(expr).0 },
+        );
+    }
+}
 
-                weight => panic!("invalid weight: {}", weight),
+/// Applies the changes to a single output relation onto its local mirror
+fn apply_changes<T, F>(
+    values: &mut Vec<T>,
+    delta: &mut DeltaMap<DDValue>,
+    relation: Relations,
+    from_ddvalue: F,
+) where
+    T: PartialEq,
+    F: Fn(DDValue) -> T,
+{
+    for (value, weight) in delta.clear_rel(relation as RelId) {
+        let value = from_ddvalue(value);
+
+        match weight {
+            1 => values.push(value),
+            -1 => {
+                let idx = values.iter().position(|v| v == &value).unwrap();
+                values.remove(idx);
             }
+
+            weight => panic!("invalid weight: {}", weight),
         }
     }
 }
@@ -246,6 +1227,7 @@ struct DatalogInner {
     scope_id: u32,
     function_id: u32,
     expression_id: u32,
+    import_id: u32,
 }
 
 impl DatalogInner {
@@ -267,6 +1249,12 @@ impl DatalogInner {
         temp
     }
 
+    pub fn inc_import(&mut self) -> u32 {
+        let temp = self.import_id;
+        self.import_id += 1;
+        temp
+    }
+
     fn push_scope(&mut self, scope: InputScope) {
         self.updates.push(Update::Insert {
             relid: Relations::InputScope as RelId,
@@ -314,9 +1302,6 @@ impl<'ddlog> DatalogTransaction<'ddlog> {
 
         let delta = datalog.hddlog.transaction_commit_dump_changes()?;
 
-        println!("State after transaction");
-        dump_delta(&delta);
-
         Ok(delta)
     }
 }
@@ -357,6 +1342,10 @@ impl<'ddlog> Scope<'ddlog> {
         self.datalog.borrow_mut().inc_expression()
     }
 
+    pub fn next_import_id(&self) -> u32 {
+        self.datalog.borrow_mut().inc_import()
+    }
+
     pub fn push_function(&self, func: Function) {
         self.datalog.borrow_mut().updates.push(Update::Insert {
             relid: Relations::Function as RelId,
@@ -371,6 +1360,62 @@ impl<'ddlog> Scope<'ddlog> {
         });
     }
 
+    pub fn push_module(&self, module: Module) {
+        self.datalog.borrow_mut().updates.push(Update::Insert {
+            relid: Relations::Module as RelId,
+            v: Value::Module(module).into_ddvalue(),
+        });
+    }
+
+    pub fn push_import(&self, import: Import) {
+        self.datalog.borrow_mut().updates.push(Update::Insert {
+            relid: Relations::Import as RelId,
+            v: Value::Import(import).into_ddvalue(),
+        });
+    }
+
+    pub fn push_type_decl(&self, decl: TypeDecl) {
+        self.datalog.borrow_mut().updates.push(Update::Insert {
+            relid: Relations::TypeDecl as RelId,
+            v: Value::TypeDecl(decl).into_ddvalue(),
+        });
+    }
+
+    pub fn push_type_field(&self, field: TypeField) {
+        self.datalog.borrow_mut().updates.push(Update::Insert {
+            relid: Relations::TypeField as RelId,
+            v: Value::TypeField(field).into_ddvalue(),
+        });
+    }
+
+    pub fn push_variant(&self, variant: Variant) {
+        self.datalog.borrow_mut().updates.push(Update::Insert {
+            relid: Relations::Variant as RelId,
+            v: Value::Variant(variant).into_ddvalue(),
+        });
+    }
+
+    pub fn push_variant_field(&self, field: VariantField) {
+        self.datalog.borrow_mut().updates.push(Update::Insert {
+            relid: Relations::VariantField as RelId,
+            v: Value::VariantField(field).into_ddvalue(),
+        });
+    }
+
+    pub fn push_type_ref(&self, type_ref: TypeRef) {
+        self.datalog.borrow_mut().updates.push(Update::Insert {
+            relid: Relations::TypeRef as RelId,
+            v: Value::TypeRef(type_ref).into_ddvalue(),
+        });
+    }
+
+    pub fn push_func_param(&self, param: FuncParam) {
+        self.datalog.borrow_mut().updates.push(Update::Insert {
+            relid: Relations::FuncParam as RelId,
+            v: Value::FuncParam(param).into_ddvalue(),
+        });
+    }
+
     pub fn push_expression(&self, expr: Expression) {
         self.datalog.borrow_mut().updates.push(Update::Insert {
             relid: Relations::Expression as RelId,
@@ -385,6 +1430,132 @@ impl<'ddlog> Scope<'ddlog> {
         });
     }
 
+    pub fn push_expr_span(&self, span: ExprSpan) {
+        self.datalog.borrow_mut().updates.push(Update::Insert {
+            relid: Relations::ExprSpan as RelId,
+            v: Value::ExprSpan(span).into_ddvalue(),
+        });
+    }
+
+    pub fn push_ensure(&self, ensure: EnsureContract) {
+        self.datalog.borrow_mut().updates.push(Update::Insert {
+            relid: Relations::EnsureContract as RelId,
+            v: Value::EnsureContract(ensure).into_ddvalue(),
+        });
+    }
+
+    pub fn push_binary(&self, binary: BinaryExpr) {
+        self.datalog.borrow_mut().updates.push(Update::Insert {
+            relid: Relations::BinaryExpr as RelId,
+            v: Value::BinaryExpr(binary).into_ddvalue(),
+        });
+    }
+
+    pub fn push_unary(&self, unary: UnaryExpr) {
+        self.datalog.borrow_mut().updates.push(Update::Insert {
+            relid: Relations::UnaryExpr as RelId,
+            v: Value::UnaryExpr(unary).into_ddvalue(),
+        });
+    }
+
+    pub fn push_ascription(&self, ascription: Ascription) {
+        self.datalog.borrow_mut().updates.push(Update::Insert {
+            relid: Relations::Ascription as RelId,
+            v: Value::Ascription(ascription).into_ddvalue(),
+        });
+    }
+
+    pub fn push_tuple_arity(&self, arity: TupleArity) {
+        self.datalog.borrow_mut().updates.push(Update::Insert {
+            relid: Relations::TupleArity as RelId,
+            v: Value::TupleArity(arity).into_ddvalue(),
+        });
+    }
+
+    pub fn push_tuple_elem(&self, elem: TupleElem) {
+        self.datalog.borrow_mut().updates.push(Update::Insert {
+            relid: Relations::TupleElem as RelId,
+            v: Value::TupleElem(elem).into_ddvalue(),
+        });
+    }
+
+    pub fn push_list_elem(&self, elem: ListElem) {
+        self.datalog.borrow_mut().updates.push(Update::Insert {
+            relid: Relations::ListElem as RelId,
+            v: Value::ListElem(elem).into_ddvalue(),
+        });
+    }
+
+    pub fn push_refinement(&self, refinement: Refinement) {
+        self.datalog.borrow_mut().updates.push(Update::Insert {
+            relid: Relations::Refinement as RelId,
+            v: Value::Refinement(refinement).into_ddvalue(),
+        });
+    }
+
+    pub fn push_record_expr(&self, record: RecordExpr) {
+        self.datalog.borrow_mut().updates.push(Update::Insert {
+            relid: Relations::RecordExpr as RelId,
+            v: Value::RecordExpr(record).into_ddvalue(),
+        });
+    }
+
+    pub fn push_record_expr_field(&self, field: RecordExprField) {
+        self.datalog.borrow_mut().updates.push(Update::Insert {
+            relid: Relations::RecordExprField as RelId,
+            v: Value::RecordExprField(field).into_ddvalue(),
+        });
+    }
+
+    pub fn push_field_access(&self, access: FieldAccess) {
+        self.datalog.borrow_mut().updates.push(Update::Insert {
+            relid: Relations::FieldAccess as RelId,
+            v: Value::FieldAccess(access).into_ddvalue(),
+        });
+    }
+
+    pub fn push_lambda_param(&self, param: LambdaParam) {
+        self.datalog.borrow_mut().updates.push(Update::Insert {
+            relid: Relations::LambdaParam as RelId,
+            v: Value::LambdaParam(param).into_ddvalue(),
+        });
+    }
+
+    pub fn push_lambda_body(&self, body: LambdaBody) {
+        self.datalog.borrow_mut().updates.push(Update::Insert {
+            relid: Relations::LambdaBody as RelId,
+            v: Value::LambdaBody(body).into_ddvalue(),
+        });
+    }
+
+    pub fn push_parenthesized(&self, paren: Parenthesized) {
+        self.datalog.borrow_mut().updates.push(Update::Insert {
+            relid: Relations::Parenthesized as RelId,
+            v: Value::Parenthesized(paren).into_ddvalue(),
+        });
+    }
+
+    pub fn push_if_expr(&self, if_expr: IfExpr) {
+        self.datalog.borrow_mut().updates.push(Update::Insert {
+            relid: Relations::IfExpr as RelId,
+            v: Value::IfExpr(if_expr).into_ddvalue(),
+        });
+    }
+
+    pub fn push_match_scrutinee(&self, scrutinee: MatchScrutinee) {
+        self.datalog.borrow_mut().updates.push(Update::Insert {
+            relid: Relations::MatchScrutinee as RelId,
+            v: Value::MatchScrutinee(scrutinee).into_ddvalue(),
+        });
+    }
+
+    pub fn push_match_arm(&self, arm: MatchArm) {
+        self.datalog.borrow_mut().updates.push(Update::Insert {
+            relid: Relations::MatchArm as RelId,
+            v: Value::MatchArm(arm).into_ddvalue(),
+        });
+    }
+
     pub fn push_literal(&self, literal: Literal) {
         self.datalog.borrow_mut().updates.push(Update::Insert {
             relid: Relations::Literal as RelId,
@@ -409,33 +1580,206 @@ impl<'ddlog> Scope<'ddlog> {
 
 fn ddlog_literal(lit: ast::Literal) -> Lit {
     match lit {
-        ast::Literal::String(s) => Lit::LitStr {
-            s: internment::intern(&s.0),
-        },
+        ast::Literal::String(s) => Lit::LitStr { s: s.to_istring() },
         ast::Literal::Int(i) => Lit::LitInt { i },
         ast::Literal::Bool(b) => Lit::LitBool { b },
     }
 }
 
 fn ddlog_type(ty: ast::Type) -> Type {
+    match ty.kind {
+        // Whether the type exists is checked through its `TypeRef`
+        ast::TypeKind::Path(path) => Type::Named {
+            name: path.segments.last().unwrap().to_istring(),
+        },
+        ast::TypeKind::Generic(name) => Type::TVar {
+            name: name.to_istring(),
+        },
+        ast::TypeKind::Bool => Type::Bool,
+        ast::TypeKind::Int => Type::Int,
+        ast::TypeKind::String => Type::String,
+        // Refinements are checked separately, as far as types go they're their base type
+        ast::TypeKind::Refined(refinement) => ddlog_type(refinement.ty),
+        ast::TypeKind::Tuple(elems) if elems.is_empty() => Type::Unit,
+        ast::TypeKind::Tuple(elems) => {
+            let elems: Vec<_> = elems.into_iter().map(ddlog_type).collect();
+            Type::Tuple {
+                elems: elems.into(),
+            }
+        }
+        ast::TypeKind::List(elem) => Type::List {
+            elem: ddlog_std::ref_new(&ddlog_type(*elem)),
+        },
+        ast::TypeKind::Func(args, ret) => {
+            let args: Vec<_> = args.into_iter().map(ddlog_type).collect();
+            Type::Func {
+                args: args.into(),
+                ret: ddlog_std::ref_new(&ddlog_type(*ret)),
+            }
+        }
+    }
+}
+
+fn ddlog_binary_op(op: ast::BinaryOp) -> BinaryOp {
+    match op {
+        ast::BinaryOp::Eq => BinaryOp::OpEq,
+        ast::BinaryOp::NotEq => BinaryOp::OpNotEq,
+        ast::BinaryOp::Less => BinaryOp::OpLess,
+        ast::BinaryOp::LessEq => BinaryOp::OpLessEq,
+        ast::BinaryOp::Greater => BinaryOp::OpGreater,
+        ast::BinaryOp::GreaterEq => BinaryOp::OpGreaterEq,
+    }
+}
+
+fn ddlog_unary_op(op: ast::UnaryOp) -> UnaryOp {
+    match op {
+        ast::UnaryOp::Not => UnaryOp::OpNot,
+    }
+}
+
+fn ddlog_pattern(pattern: ast::Pattern) -> Pattern {
+    match pattern.kind {
+        ast::PatternKind::Literal(lit) => Pattern::PatLit {
+            lit: ddlog_literal(lit),
+        },
+        ast::PatternKind::Ident(name) => Pattern::PatVar {
+            name: name.to_istring(),
+        },
+        ast::PatternKind::Path(path) => {
+            let path: Vec<_> = path
+                .segments
+                .iter()
+                .map(|segment| segment.to_istring())
+                .collect();
+
+            Pattern::PatPath { path: path.into() }
+        }
+        ast::PatternKind::Tuple(elems) => {
+            let elems: Vec<_> = elems.into_iter().map(ddlog_pattern).collect();
+            Pattern::PatTuple {
+                elems: elems.into(),
+            }
+        }
+        ast::PatternKind::Constructor(name, args) => {
+            let args: Vec<_> = args.into_iter().map(ddlog_pattern).collect();
+            Pattern::PatCtor {
+                name: name.to_istring(),
+                args: args.into(),
+            }
+        }
+    }
+}
+
+fn display_type(ty: &Type) -> String {
     match ty {
-        ast::Type::Path(_) => Type::Unknown,
-        ast::Type::Bool => Type::Bool,
-        ast::Type::Int => Type::Int,
-        ast::Type::String => Type::String,
+        Type::Bool => "Bool".to_owned(),
+        Type::Int => "Int".to_owned(),
+        Type::String => "String".to_owned(),
+        Type::Unit => "()".to_owned(),
+        Type::Unknown | Type::Poison => "_".to_owned(),
+        // Instance variables are shown as the variable of the signature they came from
+        Type::TVar { name } => {
+            let name = name.as_str();
+            format!("'{}", name.rsplit_once('.').map_or(name, |(_, var)| var))
+        }
+        Type::Named { name } => name.as_str().to_owned(),
+        Type::Func { args, ret } => {
+            let args: Vec<String> = args.iter().map(display_type).collect();
+            format!("fn({}) -> {}", args.join(", "), display_type(ret))
+        }
+        Type::Tuple { elems } => {
+            let elems: Vec<String> = elems.iter().map(display_type).collect();
+            format!("({})", elems.join(", "))
+        }
+        Type::List { elem } => match &**elem {
+            Type::Func { .. } | Type::List { .. } => format!("List ({})", display_type(elem)),
+            _ => format!("List {}", display_type(elem)),
+        },
     }
 }
 
-fn expr_kind(expr: &ast::Expr) -> ExprKind {
+/// The span and description of a syntax error
+fn syntax_error(
+    error: &ParseError<usize, lexer::Token<'_>, lexer::LexError>,
+) -> (ast::Span, String) {
+    match error {
+        ParseError::InvalidToken { location } => (
+            ast::Span::new(*location, *location),
+            "Invalid token".to_owned(),
+        ),
+
+        ParseError::UnrecognizedEOF { location, expected } => (
+            ast::Span::new(*location, *location),
+            format!("Unexpected end of file{}", expected_tokens(expected)),
+        ),
+
+        ParseError::UnrecognizedToken {
+            token: (start, token, end),
+            expected,
+        } => (
+            ast::Span::new(*start, *end),
+            format!("Unexpected `{}`{}", token, expected_tokens(expected)),
+        ),
+
+        ParseError::ExtraToken {
+            token: (start, token, end),
+        } => (
+            ast::Span::new(*start, *end),
+            format!("Unexpected `{}`", token),
+        ),
+
+        ParseError::User { error } => (error.span, error.to_string()),
+    }
+}
+
+fn expected_tokens(expected: &[String]) -> String {
+    match expected {
+        [] => String::new(),
+        [only] => format!(", expected {}", only),
+        [init @ .., last] => format!(", expected one of {} or {}", init.join(", "), last),
+    }
+}
+
+fn ddlog_span(span: ast::Span) -> Span {
+    Span {
+        start: span.start as u32,
+        end: span.end as u32,
+    }
+}
+
+/// Formats a span as `file:line:col`, lines and columns are both one-indexed
+fn location(file: &str, source: &str, span: &Span) -> String {
+    let offset = (span.start as usize).min(source.len());
+    let before = &source[..offset];
+
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    let column = before[line_start..].chars().count() + 1;
+
+    format!("{}:{}:{}", file, line, column)
+}
+
+fn expr_kind(expr: &ast::ExprKind) -> ExprKind {
     match expr {
-        ast::Expr::Let(_) => ExprKind::Decl,
-        ast::Expr::Literal(_) => ExprKind::Lit,
-        ast::Expr::Var(var) => ExprKind::Var {
-            v: internment::intern(&var.0),
+        ast::ExprKind::Let(_) => ExprKind::Decl,
+        ast::ExprKind::Literal(_) => ExprKind::Lit,
+        ast::ExprKind::Var(var) => ExprKind::Var {
+            v: var.to_istring(),
         },
-        ast::Expr::App(_) => ExprKind::App,
-
-        _ => todo!(),
+        ast::ExprKind::App(_) => ExprKind::App,
+        ast::ExprKind::Match(_) => ExprKind::Match,
+        ast::ExprKind::If(_) => ExprKind::If,
+        ast::ExprKind::Ensure(_) => ExprKind::Ensure,
+        ast::ExprKind::Binary(_) => ExprKind::Binary,
+        ast::ExprKind::Unary(_) => ExprKind::Unary,
+        ast::ExprKind::Ascribed(_) => ExprKind::Ascribed,
+        ast::ExprKind::Lambda(_) => ExprKind::Lambda,
+        ast::ExprKind::Paren(_) => ExprKind::Paren,
+        ast::ExprKind::Tuple(_) => ExprKind::TupleExpr,
+        ast::ExprKind::List(_) => ExprKind::ListExpr,
+        ast::ExprKind::Record(_) => ExprKind::Record,
+        ast::ExprKind::Field(_) => ExprKind::Field,
+        ast::ExprKind::Error => ExprKind::Error,
     }
 }
 
@@ -447,3 +1791,38 @@ fn dump_delta(delta: &DeltaMap<DDValue>) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::check;
+
+    #[test]
+    fn generic_functions_are_instantiated_at_every_use() {
+        let source = "\
+            fn id x: 'a -> 'a = x\n\
+            fn twice f: fn('a) -> 'a x: 'a -> 'a = f (f x)\n\
+            fn main =\n    \
+                let xs := map id [1, 2];\n    \
+                let names := map id [\"a\"];\n    \
+                ensure twice id 1 == len xs\n\
+        ";
+
+        assert!(check("<test>", source, false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn list_builtins_can_be_used_together() {
+        let source = "\
+            fn id x: 'a -> 'a = x\n\
+            fn add x: Int y: Int -> Int = x\n\
+            fn main =\n    \
+                let xs := cons 1 [2, 3];\n    \
+                let ys := map id xs;\n    \
+                let total := fold add 0 ys;\n    \
+                let n := len (map id [\"a\"]);\n    \
+                ensure total == n\n\
+        ";
+
+        assert!(check("<test>", source, false).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,251 @@
+//! A Wadler-style pretty printing engine
+//!
+//! Documents are built out of text and line breaks, [`Doc::Group`]s are laid out
+//! on a single line when they fit within the target width and have every one of
+//! their [`Doc::Line`]s broken otherwise
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Doc {
+    Text(String),
+    /// A space, or a newline if the enclosing group is broken
+    Line,
+    /// Nothing, or a newline if the enclosing group is broken
+    SoftLine,
+    /// A newline, which breaks every group containing it
+    HardLine,
+    /// Text that's held back until the end of the line it's on, which breaks every
+    /// group it's in so nothing else gets pushed onto that line
+    LineSuffix(String),
+    /// Indents every line break within the document
+    Nest(usize, Box<Doc>),
+    Group(Box<Doc>),
+    Concat(Vec<Doc>),
+}
+
+impl Doc {
+    pub fn text<T: Into<String>>(text: T) -> Self {
+        Self::Text(text.into())
+    }
+
+    pub fn line_suffix<T: Into<String>>(text: T) -> Self {
+        Self::LineSuffix(text.into())
+    }
+
+    pub fn nest(indent: usize, doc: Doc) -> Self {
+        Self::Nest(indent, Box::new(doc))
+    }
+
+    pub fn group(doc: Doc) -> Self {
+        Self::Group(Box::new(doc))
+    }
+
+    pub fn concat<I: IntoIterator<Item = Doc>>(docs: I) -> Self {
+        Self::Concat(docs.into_iter().collect())
+    }
+
+    /// Renders the document, breaking groups that would go past `width` columns
+    pub fn render(&self, width: usize) -> String {
+        let mut output = String::new();
+        let mut column = 0;
+        // Indentation is only written once something follows it so that blank lines stay blank
+        let mut pending_indent = None;
+        let mut line_suffixes = String::new();
+
+        let mut stack = vec![(0, Mode::Break, self)];
+        while let Some((indent, mode, doc)) = stack.pop() {
+            match doc {
+                Doc::Text(text) => {
+                    if let Some(indent) = pending_indent.take() {
+                        output.extend(std::iter::repeat_n(' ', indent));
+                    }
+
+                    output.push_str(text);
+                    column = match text.rfind('\n') {
+                        Some(newline) => text[newline + 1..].chars().count(),
+                        None => column + text.chars().count(),
+                    };
+                }
+
+                Doc::Line if mode == Mode::Flat => {
+                    output.push(' ');
+                    column += 1;
+                }
+                Doc::SoftLine if mode == Mode::Flat => {}
+                Doc::Line | Doc::SoftLine | Doc::HardLine => {
+                    output.push_str(&line_suffixes);
+                    line_suffixes.clear();
+                    output.push('\n');
+                    column = indent;
+                    pending_indent = Some(indent);
+                }
+
+                Doc::LineSuffix(text) => line_suffixes.push_str(text),
+
+                Doc::Nest(extra, doc) => stack.push((indent + extra, mode, doc)),
+
+                Doc::Group(doc) => {
+                    let mode = if mode == Mode::Flat
+                        || fits(
+                            width.saturating_sub(column),
+                            (indent, Mode::Flat, doc),
+                            &stack,
+                        ) {
+                        Mode::Flat
+                    } else {
+                        Mode::Break
+                    };
+
+                    stack.push((indent, mode, doc));
+                }
+
+                Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc))),
+            }
+        }
+
+        output.push_str(&line_suffixes);
+        output
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+/// Whether `next` laid out flat, along with everything after it up until the
+/// next line break, fits within `remaining` columns
+fn fits(mut remaining: usize, next: (usize, Mode, &Doc), rest: &[(usize, Mode, &Doc)]) -> bool {
+    let mut rest = rest.iter().rev();
+    let mut stack = vec![next];
+
+    loop {
+        let (indent, mode, doc) = match stack.pop() {
+            Some(next) => next,
+            None => match rest.next() {
+                Some(&next) => next,
+                None => return true,
+            },
+        };
+
+        match doc {
+            Doc::Text(text) => {
+                if text.contains('\n') {
+                    return false;
+                }
+
+                match remaining.checked_sub(text.chars().count()) {
+                    Some(left) => remaining = left,
+                    None => return false,
+                }
+            }
+
+            Doc::Line if mode == Mode::Flat => match remaining.checked_sub(1) {
+                Some(left) => remaining = left,
+                None => return false,
+            },
+            Doc::SoftLine if mode == Mode::Flat => {}
+            Doc::HardLine | Doc::LineSuffix(_) if mode == Mode::Flat => return false,
+            Doc::LineSuffix(_) => {}
+            Doc::Line | Doc::SoftLine | Doc::HardLine => return true,
+
+            Doc::Nest(extra, doc) => stack.push((indent + extra, mode, doc)),
+            Doc::Group(doc) => stack.push((indent, mode, doc)),
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `[a, b, c]` with every element on its own line when it doesn't fit
+    fn list(elems: &[&str]) -> Doc {
+        let mut inner = Vec::new();
+        for (idx, elem) in elems.iter().enumerate() {
+            if idx != 0 {
+                inner.extend(vec![Doc::text(","), Doc::Line]);
+            }
+            inner.push(Doc::text(*elem));
+        }
+
+        Doc::group(Doc::concat(vec![
+            Doc::text("["),
+            Doc::nest(4, Doc::concat(vec![Doc::SoftLine, Doc::concat(inner)])),
+            Doc::SoftLine,
+            Doc::text("]"),
+        ]))
+    }
+
+    #[test]
+    fn groups_that_fit_stay_flat() {
+        assert_eq!(list(&["a", "b", "c"]).render(9), "[a, b, c]");
+    }
+
+    #[test]
+    fn groups_that_dont_fit_break_every_line() {
+        assert_eq!(
+            list(&["a", "b", "c"]).render(8),
+            "[\n    a,\n    b,\n    c\n]"
+        );
+    }
+
+    #[test]
+    fn inner_groups_break_separately() {
+        let doc = Doc::group(Doc::concat(vec![
+            Doc::text("f"),
+            Doc::nest(2, Doc::concat(vec![Doc::Line, list(&["aaa", "bbb"])])),
+            Doc::nest(2, Doc::concat(vec![Doc::Line, list(&["c"])])),
+        ]));
+
+        assert_eq!(doc.render(20), "f [aaa, bbb] [c]");
+        assert_eq!(doc.render(12), "f\n  [aaa, bbb]\n  [c]");
+        assert_eq!(doc.render(8), "f\n  [\n      aaa,\n      bbb\n  ]\n  [c]");
+    }
+
+    #[test]
+    fn hard_lines_break_their_groups() {
+        let doc = Doc::group(Doc::concat(vec![
+            Doc::text("a"),
+            Doc::Line,
+            Doc::text("b"),
+            Doc::HardLine,
+            Doc::text("c"),
+        ]));
+
+        assert_eq!(doc.render(80), "a\nb\nc");
+    }
+
+    #[test]
+    fn blank_lines_have_no_indentation() {
+        let doc = Doc::nest(
+            4,
+            Doc::concat(vec![
+                Doc::text("a"),
+                Doc::HardLine,
+                Doc::HardLine,
+                Doc::text("b"),
+            ]),
+        );
+
+        assert_eq!(doc.render(80), "a\n\n    b");
+    }
+
+    #[test]
+    fn line_suffixes_go_at_the_end_of_their_line() {
+        let doc = Doc::group(Doc::concat(vec![
+            Doc::text("a"),
+            Doc::line_suffix(" -- about a"),
+            Doc::text(","),
+            Doc::Line,
+            Doc::text("b"),
+        ]));
+
+        assert_eq!(doc.render(80), "a, -- about a\nb");
+        assert_eq!(
+            Doc::concat(vec![Doc::text("a"), Doc::line_suffix(" -- end")]).render(80),
+            "a -- end",
+        );
+    }
+}
@@ -0,0 +1,605 @@
+//! Static verification of refinement types
+//!
+//! Applying a function with refined parameters, ascribing a refined type and returning from a
+//! function with a refined return type all create an obligation that the value satisfies the
+//! refinement's predicate. Obligations are discharged by [`lia`] using whatever is known at that
+//! point: the refinements of the enclosing function's parameters, earlier `ensure` contracts,
+//! integer `let` bindings and the refined return types of called functions
+
+use crate::{
+    ast::{self, Ident, Span},
+    lia::{self, Atom, Formula, Linear, Relation, Var, Verdict},
+};
+use std::collections::HashMap;
+
+/// A refinement that couldn't be proven to hold
+#[derive(Debug, Clone)]
+pub struct Obligation {
+    pub span: Span,
+    /// The predicate with the refinement's binder replaced by the checked value
+    pub constraint: String,
+    /// Either [`Verdict::Refuted`] or [`Verdict::Unknown`]
+    pub verdict: Verdict,
+}
+
+pub fn check(source: &str, items: &[ast::Item]) -> Vec<Obligation> {
+    let root = Module::new(items);
+    let mut checker = Checker {
+        source,
+        names: Vec::new(),
+        failures: Vec::new(),
+    };
+    checker.check_items(&mut vec![&root], items);
+
+    checker.failures
+}
+
+/// The functions, modules and imports declared by a list of items
+struct Module<'a> {
+    functions: HashMap<Ident, &'a ast::FuncDef>,
+    modules: HashMap<Ident, Module<'a>>,
+    imports: Vec<&'a ast::Path>,
+}
+
+impl<'a> Module<'a> {
+    fn new(items: &'a [ast::Item]) -> Self {
+        let mut module = Self {
+            functions: HashMap::new(),
+            modules: HashMap::new(),
+            imports: Vec::new(),
+        };
+
+        for item in items {
+            match item {
+                ast::Item::Func(func) => {
+                    module.functions.insert(func.name, func);
+                }
+                ast::Item::Module(child) => {
+                    module.modules.insert(child.name, Module::new(&child.items));
+                }
+                ast::Item::Usage(usage) => module.imports.push(&usage.path),
+                ast::Item::Type(_) | ast::Item::Error(_) => {}
+            }
+        }
+
+        module
+    }
+}
+
+/// Finds the function that `name` refers to from within the innermost of `scopes`
+fn lookup<'a>(scopes: &[&Module<'a>], name: Ident) -> Option<&'a ast::FuncDef> {
+    for (depth, module) in scopes.iter().enumerate().rev() {
+        if let Some(&func) = module.functions.get(&name) {
+            return Some(func);
+        }
+
+        for path in module.imports.iter() {
+            match path.segments.split_last() {
+                Some((last, modules)) if *last == name => {
+                    let func = resolve(&scopes[..=depth], modules)
+                        .and_then(|module| module.functions.get(&name).copied());
+
+                    if func.is_some() {
+                        return func;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    None
+}
+
+/// Resolves a path of module names from within the innermost of `scopes`
+fn resolve<'m, 'a>(scopes: &[&'m Module<'a>], path: &[ast::Ident]) -> Option<&'m Module<'a>> {
+    let (first, rest) = path.split_first()?;
+    let mut module = scopes
+        .iter()
+        .rev()
+        .find_map(|module| module.modules.get(first))?;
+
+    for segment in rest {
+        module = module.modules.get(segment)?;
+    }
+
+    Some(module)
+}
+
+/// What's known at a given point within a function
+#[derive(Debug, Clone, Default)]
+struct Env {
+    /// The value of every variable in scope, or `None` if it isn't an integer
+    vars: HashMap<Ident, Option<Linear>>,
+    hypotheses: Vec<Formula>,
+}
+
+struct Checker<'src> {
+    source: &'src str,
+    /// The display name of every solver variable in the current function
+    names: Vec<String>,
+    failures: Vec<Obligation>,
+}
+
+impl Checker<'_> {
+    fn check_items<'m, 'a>(&mut self, scopes: &mut Vec<&'m Module<'a>>, items: &'a [ast::Item]) {
+        for item in items {
+            match item {
+                ast::Item::Func(func) => self.check_function(scopes, func),
+
+                ast::Item::Module(module) => {
+                    let parent: &'m Module<'a> = scopes[scopes.len() - 1];
+                    if let Some(child) = parent.modules.get(&module.name) {
+                        scopes.push(child);
+                        self.check_items(scopes, &module.items);
+                        scopes.pop();
+                    }
+                }
+
+                ast::Item::Usage(_) | ast::Item::Type(_) | ast::Item::Error(_) => {}
+            }
+        }
+    }
+
+    fn check_function(&mut self, scopes: &[&Module<'_>], func: &ast::FuncDef) {
+        self.names.clear();
+
+        let mut env = Env::default();
+        for (pattern, ty) in func.params.iter() {
+            match &pattern.kind {
+                ast::PatternKind::Ident(name) => {
+                    let value = if is_int(ty) {
+                        Some(Linear::var(self.fresh(name.to_string())))
+                    } else {
+                        None
+                    };
+
+                    // Parameters can be assumed to satisfy their refinements, they're
+                    // checked at every call site instead
+                    for refinement in refinements(ty) {
+                        if let Some(predicate) = bind(refinement, value.clone(), &env) {
+                            env.hypotheses.push(predicate);
+                        }
+                    }
+
+                    env.vars.insert(*name, value);
+                }
+
+                _ => shadow(&mut env, pattern),
+            }
+        }
+        let params = env.clone();
+
+        for expr in func.body.iter() {
+            self.walk(scopes, expr, &mut env);
+        }
+
+        if let (Some(ret), Some(last)) = (func.ret.as_ref(), func.body.last()) {
+            let value = self.value(scopes, last, &mut env, ret);
+            self.require(ret, value, &params, last.span, &env);
+        }
+    }
+
+    /// Checks every obligation within an expression, recording what's learned along the way
+    fn walk(&mut self, scopes: &[&Module<'_>], expr: &ast::Expr, env: &mut Env) {
+        match &expr.kind {
+            ast::ExprKind::Let(binding) => {
+                self.walk(scopes, &binding.value, env);
+
+                match &binding.binding.kind {
+                    ast::PatternKind::Ident(name) => {
+                        let value = self.value_of(scopes, &binding.value, env);
+                        env.vars.insert(*name, value);
+                    }
+                    _ => shadow(env, &binding.binding),
+                }
+            }
+
+            ast::ExprKind::Ensure(ensure) => {
+                self.walk(scopes, &ensure.contract, env);
+
+                // Anything after a contract can rely on it holding
+                if let Some(contract) = formula(&ensure.contract, env) {
+                    env.hypotheses.push(contract);
+                }
+            }
+
+            ast::ExprKind::Match(match_expr) => {
+                self.walk(scopes, &match_expr.scrutinee, env);
+                let scrutinee = term(&match_expr.scrutinee, env);
+
+                for arm in match_expr.arms.iter() {
+                    let mut arm_env = env.clone();
+
+                    match &arm.pattern.kind {
+                        ast::PatternKind::Ident(name) => {
+                            arm_env.vars.insert(*name, scrutinee.clone());
+                        }
+                        ast::PatternKind::Literal(ast::Literal::Int(int)) => {
+                            if let Some(scrutinee) = scrutinee.clone() {
+                                arm_env.hypotheses.push(Formula::Atom(Atom::new(
+                                    scrutinee,
+                                    Relation::Eq,
+                                    Linear::constant(*int as i128),
+                                )));
+                            }
+                        }
+                        _ => shadow(&mut arm_env, &arm.pattern),
+                    }
+
+                    self.walk(scopes, &arm.body, &mut arm_env);
+                }
+            }
+
+            // Each branch can rely on the condition having gone its way
+            ast::ExprKind::If(if_expr) => {
+                self.walk(scopes, &if_expr.cond, env);
+                let cond = formula(&if_expr.cond, env);
+
+                let mut then_env = env.clone();
+                then_env.hypotheses.extend(cond.clone());
+                self.walk(scopes, &if_expr.then_branch, &mut then_env);
+
+                let mut else_env = env.clone();
+                else_env.hypotheses.extend(cond.map(Formula::not));
+                self.walk(scopes, &if_expr.else_branch, &mut else_env);
+            }
+
+            ast::ExprKind::App(app) => {
+                self.walk(scopes, &app.func, env);
+                for arg in app.args.iter() {
+                    self.walk(scopes, arg, env);
+                }
+
+                if let Some(func) = callee(scopes, &app.func, env) {
+                    self.check_application(scopes, func, &app.args, env);
+                }
+            }
+
+            ast::ExprKind::Ascribed(ascription) => {
+                self.walk(scopes, &ascription.expr, env);
+
+                let value = self.value(scopes, &ascription.expr, env, &ascription.ty);
+                self.require(&ascription.ty, value, env, ascription.expr.span, env);
+            }
+
+            ast::ExprKind::Binary(binary) => {
+                self.walk(scopes, &binary.lhs, env);
+                self.walk(scopes, &binary.rhs, env);
+            }
+            ast::ExprKind::Unary(unary) => self.walk(scopes, &unary.expr, env),
+
+            // Nothing is known about the arguments a lambda will be called with, but it can
+            // still rely on everything known about the variables it captures
+            ast::ExprKind::Lambda(lambda) => {
+                let mut lambda_env = env.clone();
+                for (pattern, _) in lambda.params.iter() {
+                    shadow(&mut lambda_env, pattern);
+                }
+
+                self.walk(scopes, &lambda.body, &mut lambda_env);
+            }
+
+            ast::ExprKind::Paren(inner) => self.walk(scopes, inner, env),
+            ast::ExprKind::Tuple(elems) | ast::ExprKind::List(elems) => {
+                for elem in elems.iter() {
+                    self.walk(scopes, elem, env);
+                }
+            }
+            ast::ExprKind::Record(record) => {
+                for field in record.fields.iter() {
+                    self.walk(scopes, &field.value, env);
+                }
+            }
+            ast::ExprKind::Field(access) => self.walk(scopes, &access.record, env),
+
+            ast::ExprKind::Literal(_) | ast::ExprKind::Var(_) | ast::ExprKind::Error => {}
+        }
+    }
+
+    /// Checks the arguments of an application against the function's parameters, returning
+    /// the parameters as they're bound within the function
+    fn check_application(
+        &mut self,
+        scopes: &[&Module<'_>],
+        func: &ast::FuncDef,
+        args: &[ast::Expr],
+        env: &mut Env,
+    ) -> Env {
+        let mut callee = Env::default();
+        for ((pattern, ty), arg) in func.params.iter().zip(args) {
+            let value = self.value(scopes, arg, env, ty);
+            self.require(ty, value.clone(), &callee, arg.span, env);
+
+            match &pattern.kind {
+                ast::PatternKind::Ident(name) => {
+                    callee.vars.insert(*name, value);
+                }
+                _ => shadow(&mut callee, pattern),
+            }
+        }
+
+        callee
+    }
+
+    /// Requires that `value` satisfies every refinement of `ty`, with any other names
+    /// within the refinements resolved using `bindings`
+    fn require(
+        &mut self,
+        ty: &ast::Type,
+        value: Option<Linear>,
+        bindings: &Env,
+        span: Span,
+        env: &Env,
+    ) {
+        for refinement in refinements(ty) {
+            let (constraint, verdict) = match bind(refinement, value.clone(), bindings) {
+                Some(goal) => (
+                    goal.display(&self.names).to_string(),
+                    lia::check(&env.hypotheses, &goal),
+                ),
+                None => (self.text(refinement.predicate.span), Verdict::Unknown),
+            };
+
+            if verdict != Verdict::Proven {
+                self.failures.push(Obligation {
+                    span,
+                    constraint,
+                    verdict,
+                });
+            }
+        }
+    }
+
+    /// The value of an expression that's expected to have the type `ty`, integers
+    /// that can't be pinned down become fresh variables
+    fn value(
+        &mut self,
+        scopes: &[&Module<'_>],
+        expr: &ast::Expr,
+        env: &mut Env,
+        ty: &ast::Type,
+    ) -> Option<Linear> {
+        self.value_of(scopes, expr, env).or_else(|| {
+            if is_int(ty) {
+                Some(Linear::var(self.fresh(self.text(expr.span))))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// The value of an expression if it's known to be an integer, anything the
+    /// expression's type guarantees is added to the hypotheses
+    fn value_of(
+        &mut self,
+        scopes: &[&Module<'_>],
+        expr: &ast::Expr,
+        env: &mut Env,
+    ) -> Option<Linear> {
+        if let Some(term) = term(expr, env) {
+            return Some(term);
+        }
+
+        let (ty, bindings) = match &expr.kind {
+            ast::ExprKind::Paren(inner) => return self.value_of(scopes, inner, env),
+
+            // Ascriptions have already been checked, so they can be relied on
+            ast::ExprKind::Ascribed(ascription) => (&ascription.ty, env.clone()),
+
+            ast::ExprKind::App(app) => {
+                let func = callee(scopes, &app.func, env)?;
+
+                // Obligations were already reported while walking the application
+                let failures = self.failures.len();
+                let params = self.check_application(scopes, func, &app.args, env);
+                self.failures.truncate(failures);
+
+                (func.ret.as_ref()?, params)
+            }
+
+            _ => return None,
+        };
+
+        if !is_int(ty) {
+            return None;
+        }
+
+        let value = Linear::var(self.fresh(self.text(expr.span)));
+        for refinement in refinements(ty) {
+            if let Some(predicate) = bind(refinement, Some(value.clone()), &bindings) {
+                env.hypotheses.push(predicate);
+            }
+        }
+
+        Some(value)
+    }
+
+    fn fresh(&mut self, name: String) -> Var {
+        self.names.push(name);
+        (self.names.len() - 1) as Var
+    }
+
+    fn text(&self, span: Span) -> String {
+        self.source
+            .get(span.start..span.end)
+            .unwrap_or("_")
+            .to_owned()
+    }
+}
+
+/// The function being applied, if it's a top-level function that isn't shadowed by a local
+fn callee<'a>(scopes: &[&Module<'a>], func: &ast::Expr, env: &Env) -> Option<&'a ast::FuncDef> {
+    match &func.kind {
+        ast::ExprKind::Var(name) if !env.vars.contains_key(name) => lookup(scopes, *name),
+        ast::ExprKind::Paren(inner) => callee(scopes, inner, env),
+        _ => None,
+    }
+}
+
+/// The predicate of a refinement with its binder bound to `value`
+fn bind(refinement: &ast::Refinement, value: Option<Linear>, bindings: &Env) -> Option<Formula> {
+    let mut env = bindings.clone();
+    env.vars.insert(refinement.binder, value);
+
+    formula(&refinement.predicate, &env)
+}
+
+/// Makes every variable bound by a pattern opaque
+fn shadow(env: &mut Env, pattern: &ast::Pattern) {
+    match &pattern.kind {
+        ast::PatternKind::Ident(name) => {
+            env.vars.insert(*name, None);
+        }
+        ast::PatternKind::Tuple(elems) | ast::PatternKind::Constructor(_, elems) => {
+            for elem in elems.iter() {
+                shadow(env, elem);
+            }
+        }
+        ast::PatternKind::Literal(_) | ast::PatternKind::Path(_) => {}
+    }
+}
+
+/// Translates a boolean expression into a formula
+fn formula(expr: &ast::Expr, env: &Env) -> Option<Formula> {
+    match &expr.kind {
+        ast::ExprKind::Literal(ast::Literal::Bool(true)) => Some(Formula::True),
+        ast::ExprKind::Literal(ast::Literal::Bool(false)) => Some(Formula::False),
+
+        ast::ExprKind::Binary(binary) => Some(Formula::Atom(Atom::new(
+            term(&binary.lhs, env)?,
+            relation(binary.op),
+            term(&binary.rhs, env)?,
+        ))),
+
+        ast::ExprKind::Unary(unary) => match unary.op {
+            ast::UnaryOp::Not => Some(formula(&unary.expr, env)?.not()),
+        },
+
+        // A conditional holds when the branch its condition picks does
+        ast::ExprKind::If(if_expr) => {
+            let cond = formula(&if_expr.cond, env)?;
+
+            Some(Formula::Or(vec![
+                Formula::And(vec![cond.clone(), formula(&if_expr.then_branch, env)?]),
+                Formula::And(vec![cond.not(), formula(&if_expr.else_branch, env)?]),
+            ]))
+        }
+
+        ast::ExprKind::Paren(inner) => formula(inner, env),
+        ast::ExprKind::Ascribed(ascription) => formula(&ascription.expr, env),
+
+        _ => None,
+    }
+}
+
+/// Translates an integer expression into a linear combination
+fn term(expr: &ast::Expr, env: &Env) -> Option<Linear> {
+    match &expr.kind {
+        ast::ExprKind::Literal(ast::Literal::Int(int)) => Some(Linear::constant(*int as i128)),
+        ast::ExprKind::Var(name) => env.vars.get(name).cloned().flatten(),
+        ast::ExprKind::Paren(inner) => term(inner, env),
+        ast::ExprKind::Ascribed(ascription) => term(&ascription.expr, env),
+
+        _ => None,
+    }
+}
+
+fn relation(op: ast::BinaryOp) -> Relation {
+    match op {
+        ast::BinaryOp::Eq => Relation::Eq,
+        ast::BinaryOp::NotEq => Relation::NotEq,
+        ast::BinaryOp::Less => Relation::Less,
+        ast::BinaryOp::LessEq => Relation::LessEq,
+        ast::BinaryOp::Greater => Relation::Greater,
+        ast::BinaryOp::GreaterEq => Relation::GreaterEq,
+    }
+}
+
+/// Every refinement placed on a type, from the outermost inwards
+fn refinements(mut ty: &ast::Type) -> Vec<&ast::Refinement> {
+    let mut refinements = Vec::new();
+    while let ast::TypeKind::Refined(refinement) = &ty.kind {
+        refinements.push(&**refinement);
+        ty = &refinement.ty;
+    }
+
+    refinements
+}
+
+/// Whether the type is `Int` once all of its refinements are stripped
+fn is_int(ty: &ast::Type) -> bool {
+    let base = refinements(ty)
+        .last()
+        .map_or(ty, |refinement| &refinement.ty);
+    base.kind == ast::TypeKind::Int
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check;
+    use crate::{lia::Verdict, syntax};
+
+    /// The verdicts of every obligation that couldn't be discharged within `source`
+    fn verdicts(source: &str) -> Vec<Verdict> {
+        let items = syntax::lower(&syntax::parse(source));
+        check(source, &items)
+            .into_iter()
+            .map(|obligation| obligation.verdict)
+            .collect()
+    }
+
+    const POSITIVE: &str = "fn pos x: { n: Int => n > 0 } -> Int = x\n";
+
+    #[test]
+    fn literal_arguments() {
+        assert!(verdicts(&format!("{}fn main = pos 5\n", POSITIVE)).is_empty());
+        assert_eq!(
+            verdicts(&format!("{}fn main = pos 0\n", POSITIVE)),
+            [Verdict::Refuted],
+        );
+    }
+
+    #[test]
+    fn unconstrained_argument() {
+        assert_eq!(
+            verdicts(&format!("{}fn main y: Int = pos y\n", POSITIVE)),
+            [Verdict::Unknown],
+        );
+    }
+
+    #[test]
+    fn contracts_are_hypotheses() {
+        let source = format!(
+            "{}fn main y: Int =\n    ensure y > 2\n    pos y\n",
+            POSITIVE,
+        );
+        assert!(verdicts(&source).is_empty());
+    }
+
+    #[test]
+    fn parameter_refinements_are_hypotheses() {
+        let source = format!("{}fn main y: {{ m: Int => m >= 1 }} = pos y\n", POSITIVE);
+        assert!(verdicts(&source).is_empty());
+    }
+
+    #[test]
+    fn branches_assume_their_condition() {
+        let source = format!(
+            "{}fn main y: Int = if y > 0 then pos y else pos 1\n",
+            POSITIVE,
+        );
+        assert!(verdicts(&source).is_empty());
+
+        let source = format!("{}fn main y: Int = if y > 0 then 1 else pos y\n", POSITIVE);
+        assert_eq!(verdicts(&source), [Verdict::Refuted]);
+    }
+
+    #[test]
+    fn conditional_predicates() {
+        let source = "fn small x: { n: Int => (if n > 0 then n < 10 else n > -10) } -> Int = x\n\
+                      fn main = small 5\n\
+                      fn other = small 20\n";
+        assert_eq!(verdicts(source), [Verdict::Refuted]);
+    }
+}
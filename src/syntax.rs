@@ -0,0 +1,232 @@
+//! The lossless syntax tree
+//!
+//! Every byte of the source ends up in exactly one token of the tree, including
+//! whitespace and comments, so the file can be reproduced from it verbatim. The tree
+//! comes in two layers: immutable green nodes which only know their kind and width,
+//! and red [`SyntaxNode`]s wrapping them with their parent and offset. The typed
+//! [`nodes`] view sits on top of the red tree and is what gets lowered into the [`ast`]
+//!
+//! [`ast`]: crate::ast
+
+mod kind;
+mod lower;
+pub mod nodes;
+mod tree;
+
+pub use kind::SyntaxKind;
+pub use lower::lower;
+pub use tree::{GreenElement, GreenNode, GreenToken, SyntaxNode, SyntaxToken};
+
+use crate::{
+    ast::Span,
+    lexer::{Comment, Lexer},
+};
+use nodes::AstNode;
+use std::rc::Rc;
+
+/// A node as the parser recognised it, which only knows about the nodes within it.
+/// The tokens in between them are filled back in from the source by [`build`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseNode {
+    pub kind: SyntaxKind,
+    pub span: Span,
+    pub children: Vec<ParseNode>,
+}
+
+impl ParseNode {
+    pub fn new(kind: SyntaxKind, start: usize, end: usize, children: Vec<ParseNode>) -> Self {
+        Self {
+            kind,
+            span: Span::new(start, end),
+            children,
+        }
+    }
+}
+
+/// Builds the syntax tree of a whole file from the items the parser produced
+pub fn build(source: &str, items: Vec<ParseNode>) -> nodes::SourceFile {
+    let file = ParseNode::new(SyntaxKind::SourceFile, 0, source.len(), items);
+    let root = SyntaxNode::new_root(Rc::new(green(source, &file, 0, source.len())));
+
+    nodes::SourceFile::cast(root).unwrap()
+}
+
+/// Parses a whole file into its syntax tree along with the number of errors within it
+#[cfg(test)]
+pub(crate) fn parse_recovering(source: &str) -> (nodes::SourceFile, usize) {
+    use crate::{grammar::ItemsParser, layout::Layout};
+
+    let (mut lex_errors, mut syntax_errors) = (Vec::new(), Vec::new());
+    let items = ItemsParser::new()
+        .parse(
+            &mut syntax_errors,
+            Layout::new(source, Lexer::new(source, &mut lex_errors)),
+        )
+        .unwrap();
+
+    (build(source, items), lex_errors.len() + syntax_errors.len())
+}
+
+/// Parses a whole file into its syntax tree, panicking if there's any error within it
+#[cfg(test)]
+pub(crate) fn parse(source: &str) -> nodes::SourceFile {
+    let (file, errors) = parse_recovering(source);
+    assert_eq!(errors, 0, "{:?}", source);
+
+    file
+}
+
+/// Error recovery can leave the parser unsure of where a node starts or ends,
+/// so every node is clamped between `start` and `end` to keep them from overlapping
+fn green(source: &str, node: &ParseNode, start: usize, end: usize) -> GreenNode {
+    let mut children = Vec::new();
+    let mut pos = start;
+
+    for child in node.children.iter() {
+        let child_start = child.span.start.max(pos).min(end);
+        let child_end = child.span.end.max(child_start).min(end);
+
+        tokens(source, pos, child_start, &mut children);
+        children.push(GreenElement::Node(Rc::new(green(
+            source,
+            child,
+            child_start,
+            child_end,
+        ))));
+        pos = child_end;
+    }
+    tokens(source, pos, end, &mut children);
+
+    GreenNode::new(node.kind, children)
+}
+
+/// Lexes the source between two nodes back into tokens, keeping all of the trivia
+fn tokens(source: &str, start: usize, end: usize, children: &mut Vec<GreenElement>) {
+    let text = &source[start..end];
+
+    // Anything wrong in here was already reported when the file was first lexed
+    let (mut errors, mut comments) = (Vec::new(), Vec::new());
+    let mut spans: Vec<(Span, SyntaxKind)> = Lexer::new(text, &mut errors)
+        .with_comments(&mut comments)
+        .filter_map(Result::ok)
+        .map(|(start, token, end)| (Span::new(start, end), SyntaxKind::from_token(&token)))
+        .collect();
+    spans.extend(comments.iter().map(|&Comment { text, span }| {
+        let kind = if text.starts_with("--") {
+            SyntaxKind::LineComment
+        } else {
+            SyntaxKind::BlockComment
+        };
+
+        (span, kind)
+    }));
+    spans.sort_by_key(|(span, _)| span.start);
+
+    let mut pos = 0;
+    for (span, kind) in spans {
+        skipped(&text[pos..span.start], children);
+        children.push(token(kind, &text[span.start..span.end]));
+        pos = span.end;
+    }
+    skipped(&text[pos..], children);
+}
+
+/// Splits whatever the lexer skipped over into whitespace and the characters it rejected
+fn skipped(mut text: &str, children: &mut Vec<GreenElement>) {
+    while let Some(chr) = text.chars().next() {
+        let is_whitespace = chr.is_whitespace();
+        let len = text
+            .find(|chr: char| chr.is_whitespace() != is_whitespace)
+            .unwrap_or(text.len());
+
+        let kind = if is_whitespace {
+            SyntaxKind::Whitespace
+        } else {
+            SyntaxKind::ErrorToken
+        };
+        children.push(token(kind, &text[..len]));
+        text = &text[len..];
+    }
+}
+
+fn token(kind: SyntaxKind, text: &str) -> GreenElement {
+    GreenElement::Token(Rc::new(GreenToken::new(kind, text.to_owned())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLES: &[&str] = &[
+        crate::EXAMPLE,
+        "-- header comment\n\
+        {- block {- nested -} still -}\n\
+        \n\
+        --- Adds things\n\
+        fn   add x: Int y: { v: Int => v > 0 } -> Int =\n\
+        \x20   let s := \"hi\\n\\u{41}\";  -- trailing\n\
+        \x20   ensure x == y\n\
+        \x20   let t := (x, (1,), ());\n\
+        \x20   foo <| x 0xff (not True) t;\n\
+        \x20   match t with\n\
+        \x20   | (a, b, c) -> a\n\
+        \x20   | _ -> x\n\
+        module m =\n\
+        \x20   fn f x: 'a -> 'a = x\n\
+        end\n\
+        use m.f\n",
+        "type Shape = | Circle Int | Rect Int Int\r\n\
+        type Point = { x: Int, y: Int }\r\n\
+        \r\n\
+        fn main =\r\n\
+        \x20   let p := Point { x: 1, y: 2 };\r\n\
+        \x20   let f := \\x: Int -> if x == p.x then [x] else [];\r\n\
+        \x20   5 |> add 1 |> add 2\r\n",
+        "fn f x: Int =\n\
+        \x20   let y := @ ) ;\n\
+        \x20   g <| ) ;\n\
+        \x20 # junk\n\
+        fn h = \"unterminated\n\
+        fn = broken\n\
+        fn after a: Int = a",
+    ];
+
+    #[test]
+    fn trees_reproduce_their_source() {
+        for source in SAMPLES {
+            let (file, _) = parse_recovering(source);
+            assert_eq!(file.syntax().to_string(), *source);
+        }
+    }
+
+    #[test]
+    fn nodes_cover_their_text() {
+        fn check(node: &SyntaxNode, source: &str) {
+            let span = node.span();
+            assert_eq!(node.to_string(), &source[span.start..span.end]);
+
+            let mut pos = span.start;
+            for child in node.children_with_tokens() {
+                let child_span = match &child {
+                    tree::SyntaxElement::Node(node) => {
+                        check(node, source);
+                        node.span()
+                    }
+                    tree::SyntaxElement::Token(token) => {
+                        assert_eq!(token.text(), &source[token.span().start..token.span().end]);
+                        token.span()
+                    }
+                };
+
+                assert_eq!(child_span.start, pos);
+                pos = child_span.end;
+            }
+            assert_eq!(pos, span.end);
+        }
+
+        for source in SAMPLES {
+            let (file, _) = parse_recovering(source);
+            check(file.syntax(), source);
+        }
+    }
+}
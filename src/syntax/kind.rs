@@ -0,0 +1,182 @@
+use crate::lexer::Token;
+
+/// The kind of every token and node in the syntax tree
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SyntaxKind {
+    FnKw,
+    ModuleKw,
+    EndKw,
+    UseKw,
+    TypeKw,
+    LetKw,
+    MatchKw,
+    WithKw,
+    IfKw,
+    ThenKw,
+    ElseKw,
+    EnsureKw,
+    NotKw,
+    TrueKw,
+    FalseKw,
+    IntKw,
+    BoolKw,
+    StringKw,
+    ListKw,
+
+    Eq,
+    EqEq,
+    NotEq,
+    Less,
+    LessEq,
+    Greater,
+    GreaterEq,
+    Colon,
+    ColonEq,
+    Semicolon,
+    Arrow,
+    FatArrow,
+    Pipe,
+    LeftPipe,
+    RightPipe,
+    LeftParen,
+    RightParen,
+    LeftBrace,
+    RightBrace,
+    LeftBracket,
+    RightBracket,
+    Comma,
+    Dot,
+    Quote,
+    Backslash,
+
+    Ident,
+    StringLit,
+    IntLit,
+    DocComment,
+
+    Whitespace,
+    LineComment,
+    BlockComment,
+    /// Text the lexer couldn't make sense of
+    ErrorToken,
+
+    SourceFile,
+    FuncDef,
+    Param,
+    RetType,
+    ModuleDef,
+    Usage,
+    TypeDef,
+    RecordDef,
+    VariantDef,
+    FieldDef,
+    Path,
+
+    LetExpr,
+    EnsureExpr,
+    MatchExpr,
+    MatchArm,
+    IfExpr,
+    AppExpr,
+    PipeExpr,
+    BinaryExpr,
+    UnaryExpr,
+    AscribedExpr,
+    LambdaExpr,
+    ParenExpr,
+    TupleExpr,
+    ListExpr,
+    RecordExpr,
+    FieldInit,
+    FieldExpr,
+    LiteralExpr,
+    VarExpr,
+
+    NamedType,
+    IntType,
+    BoolType,
+    StringType,
+    TupleType,
+    ListType,
+    FuncType,
+    GenericType,
+    RefinedType,
+    ParenType,
+
+    LiteralPat,
+    IdentPat,
+    PathPat,
+    TuplePat,
+    ParenPat,
+    ConstructorPat,
+
+    /// Something that failed to parse
+    Error,
+}
+
+impl SyntaxKind {
+    /// Whether the token carries no meaning for the program
+    pub fn is_trivia(self) -> bool {
+        matches!(
+            self,
+            Self::Whitespace | Self::LineComment | Self::BlockComment
+        )
+    }
+
+    pub fn from_token(token: &Token<'_>) -> Self {
+        match token {
+            Token::Fn => Self::FnKw,
+            Token::Module => Self::ModuleKw,
+            Token::End => Self::EndKw,
+            Token::Use => Self::UseKw,
+            Token::Type => Self::TypeKw,
+            Token::Let => Self::LetKw,
+            Token::Match => Self::MatchKw,
+            Token::With => Self::WithKw,
+            Token::If => Self::IfKw,
+            Token::Then => Self::ThenKw,
+            Token::Else => Self::ElseKw,
+            Token::Ensure => Self::EnsureKw,
+            Token::Not => Self::NotKw,
+            Token::True => Self::TrueKw,
+            Token::False => Self::FalseKw,
+            Token::Int => Self::IntKw,
+            Token::Bool => Self::BoolKw,
+            Token::String => Self::StringKw,
+            Token::List => Self::ListKw,
+
+            Token::Eq => Self::Eq,
+            Token::EqEq => Self::EqEq,
+            Token::NotEq => Self::NotEq,
+            Token::Less => Self::Less,
+            Token::LessEq => Self::LessEq,
+            Token::Greater => Self::Greater,
+            Token::GreaterEq => Self::GreaterEq,
+            Token::Colon => Self::Colon,
+            Token::ColonEq => Self::ColonEq,
+            Token::Semicolon => Self::Semicolon,
+            Token::Arrow => Self::Arrow,
+            Token::FatArrow => Self::FatArrow,
+            Token::Pipe => Self::Pipe,
+            Token::LeftPipe => Self::LeftPipe,
+            Token::RightPipe => Self::RightPipe,
+            Token::LeftParen => Self::LeftParen,
+            Token::RightParen => Self::RightParen,
+            Token::LeftBrace => Self::LeftBrace,
+            Token::RightBrace => Self::RightBrace,
+            Token::LeftBracket => Self::LeftBracket,
+            Token::RightBracket => Self::RightBracket,
+            Token::Comma => Self::Comma,
+            Token::Dot => Self::Dot,
+            Token::Quote => Self::Quote,
+            Token::Backslash => Self::Backslash,
+
+            Token::Ident(_) => Self::Ident,
+            Token::StringLit(_) => Self::StringLit,
+            Token::IntLit(_) => Self::IntLit,
+            Token::DocComment(_) => Self::DocComment,
+            // Statement breaks take up no space, they're part of the whitespace before them
+            Token::Newline => Self::Whitespace,
+        }
+    }
+}
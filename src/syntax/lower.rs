@@ -0,0 +1,699 @@
+//! Lowering the typed syntax tree into the [`ast`](crate::ast)
+//!
+//! Trivia and punctuation are dropped along the way and literals are decoded. An
+//! item missing any of its pieces becomes an [`ast::Item::Error`], and an expression
+//! missing any of its pieces becomes an [`ast::ExprKind::Error`]
+
+use super::{
+    nodes::{self, AstNode},
+    SyntaxKind, SyntaxNode, SyntaxToken,
+};
+use crate::{
+    ast::{self, Ident, Span},
+    lexer::{Lexer, Token},
+};
+
+pub fn lower(file: &nodes::SourceFile) -> Vec<ast::Item> {
+    file.items().map(item).collect()
+}
+
+fn item(item: nodes::Item) -> ast::Item {
+    let lowered = match &item {
+        nodes::Item::Func(func) => func_def(func).map(ast::Item::Func),
+        nodes::Item::Module(module) => module_def(module).map(ast::Item::Module),
+        nodes::Item::Usage(usage) => self::usage(usage).map(ast::Item::Usage),
+        nodes::Item::Type(def) => type_def(def).map(ast::Item::Type),
+        nodes::Item::Error(_) => None,
+    };
+
+    lowered.unwrap_or_else(|| ast::Item::Error(item.syntax().span()))
+}
+
+fn func_def(func: &nodes::FuncDef) -> Option<ast::FuncDef> {
+    let params = func.params().map(param).collect::<Option<_>>()?;
+    let ret = match func.ret() {
+        Some(ret) => Some(ty(ret.ty()?)?),
+        None => None,
+    };
+
+    Some(ast::FuncDef {
+        docs: func.docs().filter_map(|doc| doc_comment(&doc)).collect(),
+        name: ident(func.name()?),
+        params,
+        ret,
+        body: func.body().map(expr).collect(),
+        span: item_span(func.syntax(), func.fn_token()?),
+    })
+}
+
+fn module_def(module: &nodes::ModuleDef) -> Option<ast::Module> {
+    Some(ast::Module {
+        docs: module.docs().filter_map(|doc| doc_comment(&doc)).collect(),
+        name: ident(module.name()?),
+        items: module.items().map(item).collect(),
+        span: item_span(module.syntax(), module.module_token()?),
+    })
+}
+
+fn usage(usage: &nodes::Usage) -> Option<ast::Usage> {
+    Some(ast::Usage {
+        docs: usage.docs().filter_map(|doc| doc_comment(&doc)).collect(),
+        path: path(usage.path()?),
+        span: item_span(usage.syntax(), usage.use_token()?),
+    })
+}
+
+fn type_def(def: &nodes::TypeDef) -> Option<ast::TypeDef> {
+    let kind = if let Some(record) = def.record() {
+        let fields = record
+            .fields()
+            .map(|field| {
+                Some(ast::FieldDef {
+                    name: ident(field.name()?),
+                    ty: ty(field.ty()?)?,
+                    span: field.syntax().span(),
+                })
+            })
+            .collect::<Option<_>>()?;
+
+        ast::TypeDefKind::Record(fields)
+    } else {
+        let variants = def
+            .variants()
+            .map(|variant| {
+                Some(ast::VariantDef {
+                    name: ident(variant.name()?),
+                    fields: variant.fields().map(ty).collect::<Option<_>>()?,
+                    span: variant.syntax().span(),
+                })
+            })
+            .collect::<Option<_>>()?;
+
+        ast::TypeDefKind::Variants(variants)
+    };
+
+    Some(ast::TypeDef {
+        docs: def.docs().filter_map(|doc| doc_comment(&doc)).collect(),
+        name: ident(def.name()?),
+        kind,
+        span: item_span(def.syntax(), def.type_token()?),
+    })
+}
+
+fn param(param: nodes::Param) -> Option<(ast::Pattern, ast::Type)> {
+    Some((pattern(param.pattern()?)?, ty(param.ty()?)?))
+}
+
+/// Items start at their keyword, leaving their doc comments out
+fn item_span(node: &SyntaxNode, keyword: SyntaxToken) -> Span {
+    Span::new(keyword.span().start, node.span().end)
+}
+
+fn expr(expr: nodes::Expr) -> ast::Expr {
+    let span = expr.syntax().span();
+    ast::Expr::new(expr_kind(expr).unwrap_or(ast::ExprKind::Error), span)
+}
+
+fn expr_kind(expr: nodes::Expr) -> Option<ast::ExprKind> {
+    let kind = match expr {
+        nodes::Expr::Let(binding) => ast::ExprKind::Let(Box::new(ast::Let {
+            binding: pattern(binding.pattern()?)?,
+            value: self::expr(binding.value()?),
+        })),
+
+        nodes::Expr::Ensure(ensure) => ast::ExprKind::Ensure(Box::new(ast::Ensure {
+            contract: self::expr(ensure.contract()?),
+        })),
+
+        nodes::Expr::Match(match_expr) => {
+            let arms = match_expr
+                .arms()
+                .map(|arm| {
+                    Some(ast::MatchArm {
+                        pattern: pattern(arm.pattern()?)?,
+                        body: self::expr(arm.body()?),
+                        span: arm.syntax().span(),
+                    })
+                })
+                .collect::<Option<_>>()?;
+
+            ast::ExprKind::Match(Box::new(ast::Match {
+                scrutinee: self::expr(match_expr.scrutinee()?),
+                arms,
+            }))
+        }
+
+        nodes::Expr::If(if_expr) => ast::ExprKind::If(Box::new(ast::If {
+            cond: self::expr(if_expr.cond()?),
+            then_branch: self::expr(if_expr.then_branch()?),
+            else_branch: self::expr(if_expr.else_branch()?),
+        })),
+
+        nodes::Expr::App(app) => {
+            let style = if app.left_pipe().is_some() {
+                ast::AppStyle::LeftPipe
+            } else {
+                ast::AppStyle::Juxtaposed
+            };
+
+            ast::ExprKind::App(Box::new(ast::App {
+                func: self::expr(app.func()?),
+                args: app.args().map(self::expr).collect(),
+                style,
+            }))
+        }
+
+        // `x |> f y` is the same call as `f y x`. Currying would make `(f y) x` mean the
+        // same, but flattening it keeps one application the formatter can write back as a pipe
+        nodes::Expr::Pipe(pipe) => {
+            let arg = self::expr(pipe.arg()?);
+
+            let (func, mut args) = match pipe.func()? {
+                nodes::Expr::App(app) if app.left_pipe().is_none() => (
+                    self::expr(app.func()?),
+                    app.args().map(self::expr).collect(),
+                ),
+                func => (self::expr(func), Vec::new()),
+            };
+            args.push(arg);
+
+            ast::ExprKind::App(Box::new(ast::App {
+                func,
+                args,
+                style: ast::AppStyle::Pipeline,
+            }))
+        }
+
+        nodes::Expr::Binary(binary) => {
+            let op = match binary.op()?.kind() {
+                SyntaxKind::EqEq => ast::BinaryOp::Eq,
+                SyntaxKind::NotEq => ast::BinaryOp::NotEq,
+                SyntaxKind::Less => ast::BinaryOp::Less,
+                SyntaxKind::LessEq => ast::BinaryOp::LessEq,
+                SyntaxKind::Greater => ast::BinaryOp::Greater,
+                SyntaxKind::GreaterEq => ast::BinaryOp::GreaterEq,
+                _ => return None,
+            };
+
+            ast::ExprKind::Binary(Box::new(ast::Binary {
+                lhs: self::expr(binary.lhs()?),
+                op,
+                rhs: self::expr(binary.rhs()?),
+            }))
+        }
+
+        nodes::Expr::Unary(unary) => {
+            let op = match unary.op()?.kind() {
+                SyntaxKind::NotKw => ast::UnaryOp::Not,
+                _ => return None,
+            };
+
+            ast::ExprKind::Unary(Box::new(ast::Unary {
+                op,
+                expr: self::expr(unary.expr()?),
+            }))
+        }
+
+        nodes::Expr::Ascribed(ascription) => ast::ExprKind::Ascribed(Box::new(ast::Ascription {
+            expr: self::expr(ascription.expr()?),
+            ty: ty(ascription.ty()?)?,
+        })),
+
+        nodes::Expr::Lambda(lambda) => ast::ExprKind::Lambda(Box::new(ast::Lambda {
+            params: lambda.params().map(param).collect::<Option<_>>()?,
+            body: self::expr(lambda.body()?),
+        })),
+
+        nodes::Expr::Paren(paren) => ast::ExprKind::Paren(Box::new(self::expr(paren.expr()?))),
+        nodes::Expr::Tuple(tuple) => ast::ExprKind::Tuple(tuple.elems().map(self::expr).collect()),
+        nodes::Expr::List(list) => ast::ExprKind::List(list.elems().map(self::expr).collect()),
+
+        nodes::Expr::Record(record) => {
+            let fields = record
+                .fields()
+                .map(|field| {
+                    Some(ast::FieldInit {
+                        name: ident(field.name()?),
+                        value: self::expr(field.value()?),
+                        span: field.syntax().span(),
+                    })
+                })
+                .collect::<Option<_>>()?;
+
+            ast::ExprKind::Record(Box::new(ast::Record {
+                name: ident(record.name()?),
+                fields,
+            }))
+        }
+
+        nodes::Expr::Field(access) => ast::ExprKind::Field(Box::new(ast::FieldAccess {
+            record: self::expr(access.record()?),
+            field: ident(access.field()?),
+        })),
+
+        nodes::Expr::Literal(literal) => ast::ExprKind::Literal(self::literal(&literal.token()?)?),
+        nodes::Expr::Var(var) => ast::ExprKind::Var(ident(var.name()?)),
+        nodes::Expr::Error(_) => ast::ExprKind::Error,
+    };
+
+    Some(kind)
+}
+
+fn ty(ty: nodes::Type) -> Option<ast::Type> {
+    let span = ty.syntax().span();
+
+    let kind = match ty {
+        nodes::Type::Named(named) => match named.path() {
+            Some(path) => ast::TypeKind::Path(self::path(path)),
+            None => {
+                let name = named.name()?;
+                ast::TypeKind::Path(ast::Path::new(vec![ident(name.clone())], name.span()))
+            }
+        },
+
+        nodes::Type::Int(_) => ast::TypeKind::Int,
+        nodes::Type::Bool(_) => ast::TypeKind::Bool,
+        nodes::Type::String(_) => ast::TypeKind::String,
+        nodes::Type::Tuple(tuple) => {
+            ast::TypeKind::Tuple(tuple.elems().map(self::ty).collect::<Option<_>>()?)
+        }
+        nodes::Type::List(list) => ast::TypeKind::List(Box::new(self::ty(list.elem()?)?)),
+        nodes::Type::Func(func) => {
+            let args = func.args().map(self::ty).collect::<Option<_>>()?;
+            ast::TypeKind::Func(args, Box::new(self::ty(func.ret()?.ty()?)?))
+        }
+        nodes::Type::Generic(generic) => ast::TypeKind::Generic(ident(generic.name()?)),
+
+        nodes::Type::Refined(refined) => ast::TypeKind::Refined(Box::new(ast::Refinement {
+            binder: ident(refined.binder()?),
+            ty: self::ty(refined.ty()?)?,
+            predicate: expr(refined.predicate()?),
+        })),
+
+        // Parentheses only group, the type takes on the span including them
+        nodes::Type::Paren(paren) => self::ty(paren.ty()?)?.kind,
+    };
+
+    Some(ast::Type::new(kind, span))
+}
+
+fn pattern(pattern: nodes::Pattern) -> Option<ast::Pattern> {
+    let span = pattern.syntax().span();
+
+    let kind = match pattern {
+        nodes::Pattern::Literal(literal) => {
+            ast::PatternKind::Literal(self::literal(&literal.token()?)?)
+        }
+        nodes::Pattern::Ident(binding) => {
+            let name = ident(binding.name()?);
+            if name.starts_with(char::is_uppercase) {
+                ast::PatternKind::Constructor(name, Vec::new())
+            } else {
+                ast::PatternKind::Ident(name)
+            }
+        }
+        nodes::Pattern::Path(path) => ast::PatternKind::Path(self::path(path.path()?)),
+        nodes::Pattern::Tuple(tuple) => {
+            ast::PatternKind::Tuple(tuple.elems().map(self::pattern).collect::<Option<_>>()?)
+        }
+        nodes::Pattern::Paren(paren) => self::pattern(paren.pattern()?)?.kind,
+        nodes::Pattern::Constructor(ctor) => ast::PatternKind::Constructor(
+            ident(ctor.name()?),
+            ctor.args().map(self::pattern).collect::<Option<_>>()?,
+        ),
+    };
+
+    Some(ast::Pattern::new(kind, span))
+}
+
+fn path(path: nodes::Path) -> ast::Path {
+    ast::Path::new(path.segments().map(ident).collect(), path.syntax().span())
+}
+
+fn ident(token: SyntaxToken) -> Ident {
+    Ident::new(token.text())
+}
+
+fn literal(token: &SyntaxToken) -> Option<ast::Literal> {
+    let literal = match token.kind() {
+        SyntaxKind::TrueKw => ast::Literal::Bool(true),
+        SyntaxKind::FalseKw => ast::Literal::Bool(false),
+
+        _ => match relex(token)? {
+            Token::StringLit(string) => ast::Literal::String(Ident::new(&string)),
+            Token::IntLit(int) => ast::Literal::Int(int),
+            _ => return None,
+        },
+    };
+
+    Some(literal)
+}
+
+fn doc_comment(token: &SyntaxToken) -> Option<String> {
+    match relex(token)? {
+        Token::DocComment(doc) => Some(doc.to_owned()),
+        _ => None,
+    }
+}
+
+/// Decodes a token's value by lexing it again, so it's always exactly what the lexer makes of it
+fn relex(token: &SyntaxToken) -> Option<Token<'_>> {
+    let (_, token, _) = Lexer::new(token.text(), &mut Vec::new()).next()?.ok()?;
+    Some(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::lower;
+    use crate::{
+        ast::{
+            AppStyle, BinaryOp, Expr, ExprKind, Item, Literal, Path, Pattern, PatternKind, Type,
+            TypeDefKind, TypeKind,
+        },
+        syntax::{parse, parse_recovering},
+    };
+
+    /// Every item in `source` in brief, see `item`
+    fn items(source: &str) -> Vec<String> {
+        lower(&parse(source)).iter().map(item).collect()
+    }
+
+    /// Each statement of the only function in `source` in brief, see `expr`
+    fn body(source: &str) -> Vec<String> {
+        match lower(&parse(source)).as_slice() {
+            [Item::Func(func)] => func.body.iter().map(expr).collect(),
+            items => panic!("expected a single function, found {:?}", items),
+        }
+    }
+
+    fn item(item: &Item) -> String {
+        match item {
+            Item::Func(func) => {
+                let params: String = func
+                    .params
+                    .iter()
+                    .map(|(param, param_ty)| format!(" {}: {}", pattern(param), ty(param_ty)))
+                    .collect();
+                let ret = match &func.ret {
+                    Some(ret) => format!(" -> {}", ty(ret)),
+                    None => String::new(),
+                };
+                let body: Vec<_> = func.body.iter().map(expr).collect();
+
+                format!("fn {}{}{} = {}", func.name, params, ret, body.join("; "))
+            }
+            Item::Type(def) => match &def.kind {
+                TypeDefKind::Record(fields) => {
+                    let fields: Vec<_> = fields
+                        .iter()
+                        .map(|field| format!("{}: {}", field.name, ty(&field.ty)))
+                        .collect();
+                    format!("type {} = {{ {} }}", def.name, fields.join(", "))
+                }
+                TypeDefKind::Variants(variants) => {
+                    let variants: String = variants
+                        .iter()
+                        .map(|variant| {
+                            let fields: String = variant
+                                .fields
+                                .iter()
+                                .map(|field| format!(" {}", ty(field)))
+                                .collect();
+                            format!(" | {}{}", variant.name, fields)
+                        })
+                        .collect();
+                    format!("type {} ={}", def.name, variants)
+                }
+            },
+            Item::Error(_) => "error".to_owned(),
+            item => panic!("unexpected item {:?}", item),
+        }
+    }
+
+    /// An expression written back out with every application, operator and other compound
+    /// expression parenthesised, so the way it was grouped can be read off directly.
+    /// Pipelines and left pipes are marked by `|>` and `<|` before their function
+    fn expr(expr: &Expr) -> String {
+        let list = |exprs: &[Expr]| exprs.iter().map(self::expr).collect::<Vec<_>>().join(", ");
+
+        match &expr.kind {
+            ExprKind::Let(binding) => format!(
+                "let {} := {}",
+                pattern(&binding.binding),
+                self::expr(&binding.value),
+            ),
+            ExprKind::Ensure(ensure) => format!("ensure {}", self::expr(&ensure.contract)),
+            ExprKind::Match(match_expr) => {
+                let arms: String = match_expr
+                    .arms
+                    .iter()
+                    .map(|arm| format!(" | {} -> {}", pattern(&arm.pattern), self::expr(&arm.body)))
+                    .collect();
+                format!("(match {} with{})", self::expr(&match_expr.scrutinee), arms)
+            }
+            ExprKind::If(if_expr) => format!(
+                "(if {} then {} else {})",
+                self::expr(&if_expr.cond),
+                self::expr(&if_expr.then_branch),
+                self::expr(&if_expr.else_branch),
+            ),
+            ExprKind::Literal(lit) => literal(lit),
+            ExprKind::Var(var) => var.to_string(),
+            ExprKind::App(app) => {
+                let style = match app.style {
+                    AppStyle::Juxtaposed => "",
+                    AppStyle::LeftPipe => "<| ",
+                    AppStyle::Pipeline => "|> ",
+                };
+                let args: String = app
+                    .args
+                    .iter()
+                    .map(|arg| format!(" {}", self::expr(arg)))
+                    .collect();
+                format!("({}{}{})", style, self::expr(&app.func), args)
+            }
+            ExprKind::Binary(binary) => {
+                let op = match binary.op {
+                    BinaryOp::Eq => "==",
+                    BinaryOp::NotEq => "!=",
+                    BinaryOp::Less => "<",
+                    BinaryOp::LessEq => "<=",
+                    BinaryOp::Greater => ">",
+                    BinaryOp::GreaterEq => ">=",
+                };
+                format!(
+                    "({} {} {})",
+                    self::expr(&binary.lhs),
+                    op,
+                    self::expr(&binary.rhs)
+                )
+            }
+            ExprKind::Unary(unary) => format!("(not {})", self::expr(&unary.expr)),
+            ExprKind::Ascribed(ascription) => format!(
+                "({} : {})",
+                self::expr(&ascription.expr),
+                ty(&ascription.ty),
+            ),
+            ExprKind::Lambda(lambda) => {
+                let params: String = lambda
+                    .params
+                    .iter()
+                    .map(|(param, param_ty)| format!("{}: {} ", pattern(param), ty(param_ty)))
+                    .collect();
+                format!("(\\{}-> {})", params, self::expr(&lambda.body))
+            }
+            ExprKind::Paren(inner) => format!("({})", self::expr(inner)),
+            ExprKind::Tuple(elems) => format!("({})", list(elems)),
+            ExprKind::List(elems) => format!("[{}]", list(elems)),
+            ExprKind::Record(record) => {
+                let fields: Vec<_> = record
+                    .fields
+                    .iter()
+                    .map(|field| format!("{}: {}", field.name, self::expr(&field.value)))
+                    .collect();
+                format!("{} {{ {} }}", record.name, fields.join(", "))
+            }
+            ExprKind::Field(access) => format!("{}.{}", self::expr(&access.record), access.field),
+            ExprKind::Error => "error".to_owned(),
+        }
+    }
+
+    /// A pattern written back out, with constructors parenthesised to tell them apart from bindings
+    fn pattern(pattern: &Pattern) -> String {
+        match &pattern.kind {
+            PatternKind::Literal(lit) => literal(lit),
+            PatternKind::Path(path) => self::path(path),
+            PatternKind::Ident(name) => name.to_string(),
+            PatternKind::Tuple(elems) => {
+                let elems: Vec<_> = elems.iter().map(self::pattern).collect();
+                format!("({})", elems.join(", "))
+            }
+            PatternKind::Constructor(name, args) => {
+                let args: String = args
+                    .iter()
+                    .map(|arg| format!(" {}", self::pattern(arg)))
+                    .collect();
+                format!("({}{})", name, args)
+            }
+        }
+    }
+
+    /// A type written back out, with list types parenthesised
+    fn ty(ty: &Type) -> String {
+        let list = |types: &[Type]| types.iter().map(self::ty).collect::<Vec<_>>().join(", ");
+
+        match &ty.kind {
+            TypeKind::Path(path) => self::path(path),
+            TypeKind::Bool => "Bool".to_owned(),
+            TypeKind::Int => "Int".to_owned(),
+            TypeKind::String => "String".to_owned(),
+            TypeKind::Tuple(elems) => format!("({})", list(elems)),
+            TypeKind::List(elem) => format!("(List {})", self::ty(elem)),
+            TypeKind::Func(args, ret) => format!("fn({}) -> {}", list(args), self::ty(ret)),
+            TypeKind::Generic(name) => format!("'{}", name),
+            TypeKind::Refined(refinement) => format!(
+                "{{ {}: {} => {} }}",
+                refinement.binder,
+                self::ty(&refinement.ty),
+                expr(&refinement.predicate),
+            ),
+        }
+    }
+
+    fn path(path: &Path) -> String {
+        let segments: Vec<_> = path
+            .segments
+            .iter()
+            .map(|segment| segment.as_str())
+            .collect();
+        segments.join(".")
+    }
+
+    fn literal(lit: &Literal) -> String {
+        match lit {
+            Literal::String(string) => format!("{:?}", string.as_str()),
+            Literal::Int(int) => int.to_string(),
+            Literal::Bool(true) => "True".to_owned(),
+            Literal::Bool(false) => "False".to_owned(),
+        }
+    }
+
+    #[test]
+    fn match_arms() {
+        assert_eq!(
+            body("fn f x: Int =\n    match x with\n    | 0 -> True\n    | n -> False\n"),
+            ["(match x with | 0 -> True | n -> False)"],
+        );
+    }
+
+    #[test]
+    fn nested_match() {
+        assert_eq!(
+            body(
+                "fn f x: Int y: Int =\n    match x with\n    \
+                 | 0 -> (match y with | 0 -> 1 | _ -> 2)\n    | _ -> 3\n",
+            ),
+            ["(match x with | 0 -> ((match y with | 0 -> 1 | _ -> 2)) | _ -> 3)"],
+        );
+    }
+
+    #[test]
+    fn arm_bodies_are_full_expressions() {
+        // A `match` within the `then` branch is closed off by the `else`
+        assert_eq!(
+            body(
+                "fn f x: Int y: Bool =\n\
+                \x20   match x with\n\
+                \x20   | 0 -> if y then 1 else 2\n\
+                \x20   | 1 -> \\z: Int -> z\n\
+                \x20   | 2 -> let z := 3;\n\
+                \x20   | 3 -> if y then match x with | _ -> 4 else 5\n\
+                \x20   | _ -> ensure y\n",
+            ),
+            ["(match x with \
+                 | 0 -> (if y then 1 else 2) \
+                 | 1 -> (\\z: Int -> z) \
+                 | 2 -> let z := 3 \
+                 | 3 -> (if y then (match x with | _ -> 4) else 5) \
+                 | _ -> ensure y)"],
+        );
+    }
+
+    #[test]
+    fn recovers_from_every_error() {
+        let source = "fn a = (\nfn b = 1\nfn c x: = 2\nfn d =\n    let x := ;\n    x\n";
+        let (file, errors) = parse_recovering(source);
+        assert_eq!(errors, 3);
+
+        // The broken binding keeps its place in the body so `x` is still declared
+        let items: Vec<_> = lower(&file).iter().map(item).collect();
+        assert_eq!(
+            items,
+            ["error", "fn b = 1", "error", "fn d = let x := error; x"]
+        );
+    }
+
+    #[test]
+    fn applications_bind_tighter_than_operators() {
+        assert_eq!(
+            body("fn f =\n    not f x y == g z\n    x |> f a |> g\n    h <| (i 1) j;\n"),
+            [
+                "(not ((f x y) == (g z)))",
+                "(|> g (|> f a x))",
+                "(<| h ((i 1)) j)",
+            ],
+        );
+    }
+
+    #[test]
+    fn lambdas() {
+        assert_eq!(
+            body("fn f =\n    \\x: Int g: fn(Int) -> Int -> g x\n"),
+            ["(\\x: Int g: fn(Int) -> Int -> (g x))"],
+        );
+    }
+
+    #[test]
+    fn records() {
+        assert_eq!(
+            items("type Point = { x: Int, y: Int }\nfn f =\n    (Point { x: 1, y: 2 }).y\n"),
+            [
+                "type Point = { x: Int, y: Int }",
+                "fn f = (Point { x: 1, y: 2 }).y",
+            ],
+        );
+    }
+
+    #[test]
+    fn variants_and_constructor_patterns() {
+        // A lone capitalised name is a constructor, anything else binds
+        assert_eq!(
+            items(
+                "type Shape =\n    | Circle Int\n    | Rect Int Int\n    | Empty\n\
+                 fn area s: Shape =\n    match s with\n    | Circle r -> r\n    \
+                 | Rect w h -> w\n    | Empty -> 0\n    | other -> 1\n",
+            ),
+            [
+                "type Shape = | Circle Int | Rect Int Int | Empty",
+                "fn area s: Shape = (match s with \
+                 | (Circle r) -> r | (Rect w h) -> w | (Empty) -> 0 | other -> 1)",
+            ],
+        );
+    }
+
+    #[test]
+    fn lists() {
+        assert_eq!(
+            body("fn f =\n    [1, f x, [], [2, 3]] : List (List Int)\n"),
+            ["([1, (f x), [], [2, 3]] : (List (List Int)))"],
+        );
+    }
+
+    #[test]
+    fn destructuring_patterns() {
+        assert_eq!(
+            items("fn f (a, (b, _)): (Int, (Int, Int)) =\n    let (c, Some d, 0) := x;\n    c\n"),
+            ["fn f (a, (b, _)): (Int, (Int, Int)) = let (c, (Some d), 0) := x; c"],
+        );
+    }
+}
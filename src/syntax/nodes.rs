@@ -0,0 +1,555 @@
+//! A typed view over the syntax tree
+//!
+//! Each node type wraps a [`SyntaxNode`] of the matching kind and picks its pieces
+//! out by kind. Since a tree may come from a file with syntax errors every accessor
+//! is fallible, a missing piece is just `None`
+
+use super::{SyntaxKind, SyntaxNode, SyntaxToken};
+
+pub trait AstNode: Sized {
+    fn cast(node: SyntaxNode) -> Option<Self>;
+
+    fn syntax(&self) -> &SyntaxNode;
+}
+
+macro_rules! node {
+    ($($name:ident),* $(,)?) => {
+        $(
+            #[derive(Debug, Clone)]
+            pub struct $name(SyntaxNode);
+
+            impl AstNode for $name {
+                fn cast(node: SyntaxNode) -> Option<Self> {
+                    if node.kind() == SyntaxKind::$name {
+                        Some(Self(node))
+                    } else {
+                        None
+                    }
+                }
+
+                fn syntax(&self) -> &SyntaxNode {
+                    &self.0
+                }
+            }
+        )*
+    };
+}
+
+/// An enum over every node that can appear in the same position
+macro_rules! node_enum {
+    ($name:ident { $($variant:ident($node:ident)),* $(,)? }) => {
+        #[derive(Debug, Clone)]
+        pub enum $name {
+            $($variant($node),)*
+        }
+
+        impl AstNode for $name {
+            fn cast(node: SyntaxNode) -> Option<Self> {
+                match node.kind() {
+                    $(SyntaxKind::$node => Some(Self::$variant($node(node))),)*
+                    _ => None,
+                }
+            }
+
+            fn syntax(&self) -> &SyntaxNode {
+                match self {
+                    $(Self::$variant(node) => node.syntax(),)*
+                }
+            }
+        }
+    };
+}
+
+node! {
+    SourceFile, FuncDef, Param, RetType, ModuleDef, Usage, TypeDef, RecordDef, VariantDef,
+    FieldDef, Path,
+    LetExpr, EnsureExpr, MatchExpr, MatchArm, IfExpr, AppExpr, PipeExpr, BinaryExpr, UnaryExpr,
+    AscribedExpr, LambdaExpr, ParenExpr, TupleExpr, ListExpr, RecordExpr, FieldInit, FieldExpr,
+    LiteralExpr, VarExpr,
+    NamedType, IntType, BoolType, StringType, TupleType, ListType, FuncType, GenericType, RefinedType,
+    ParenType,
+    LiteralPat, IdentPat, PathPat, TuplePat, ParenPat, ConstructorPat,
+    Error,
+}
+
+node_enum!(Item {
+    Func(FuncDef),
+    Module(ModuleDef),
+    Usage(Usage),
+    Type(TypeDef),
+    Error(Error),
+});
+
+node_enum!(Expr {
+    Let(LetExpr),
+    Ensure(EnsureExpr),
+    Match(MatchExpr),
+    If(IfExpr),
+    App(AppExpr),
+    Pipe(PipeExpr),
+    Binary(BinaryExpr),
+    Unary(UnaryExpr),
+    Ascribed(AscribedExpr),
+    Lambda(LambdaExpr),
+    Paren(ParenExpr),
+    Tuple(TupleExpr),
+    List(ListExpr),
+    Record(RecordExpr),
+    Field(FieldExpr),
+    Literal(LiteralExpr),
+    Var(VarExpr),
+    Error(Error),
+});
+
+node_enum!(Type {
+    Named(NamedType),
+    Int(IntType),
+    Bool(BoolType),
+    String(StringType),
+    Tuple(TupleType),
+    List(ListType),
+    Func(FuncType),
+    Generic(GenericType),
+    Refined(RefinedType),
+    Paren(ParenType),
+});
+
+node_enum!(Pattern {
+    Literal(LiteralPat),
+    Ident(IdentPat),
+    Path(PathPat),
+    Tuple(TuplePat),
+    Paren(ParenPat),
+    Constructor(ConstructorPat),
+});
+
+fn child<N: AstNode>(node: &SyntaxNode) -> Option<N> {
+    node.children().find_map(N::cast)
+}
+
+fn children<'a, N: AstNode + 'a>(node: &'a SyntaxNode) -> impl Iterator<Item = N> + 'a {
+    node.children().filter_map(N::cast)
+}
+
+fn token(node: &SyntaxNode, kind: SyntaxKind) -> Option<SyntaxToken> {
+    node.tokens().find(|token| token.kind() == kind)
+}
+
+fn docs(node: &SyntaxNode) -> impl Iterator<Item = SyntaxToken> + '_ {
+    node.tokens()
+        .filter(|token| token.kind() == SyntaxKind::DocComment)
+}
+
+impl SourceFile {
+    pub fn items(&self) -> impl Iterator<Item = Item> + '_ {
+        children(&self.0)
+    }
+}
+
+impl FuncDef {
+    pub fn docs(&self) -> impl Iterator<Item = SyntaxToken> + '_ {
+        docs(&self.0)
+    }
+
+    pub fn fn_token(&self) -> Option<SyntaxToken> {
+        token(&self.0, SyntaxKind::FnKw)
+    }
+
+    pub fn name(&self) -> Option<SyntaxToken> {
+        token(&self.0, SyntaxKind::Ident)
+    }
+
+    pub fn params(&self) -> impl Iterator<Item = Param> + '_ {
+        children(&self.0)
+    }
+
+    pub fn ret(&self) -> Option<RetType> {
+        child(&self.0)
+    }
+
+    pub fn body(&self) -> impl Iterator<Item = Expr> + '_ {
+        children(&self.0)
+    }
+}
+
+impl Param {
+    pub fn pattern(&self) -> Option<Pattern> {
+        child(&self.0)
+    }
+
+    pub fn ty(&self) -> Option<Type> {
+        child(&self.0)
+    }
+}
+
+impl RetType {
+    pub fn ty(&self) -> Option<Type> {
+        child(&self.0)
+    }
+}
+
+impl ModuleDef {
+    pub fn docs(&self) -> impl Iterator<Item = SyntaxToken> + '_ {
+        docs(&self.0)
+    }
+
+    pub fn module_token(&self) -> Option<SyntaxToken> {
+        token(&self.0, SyntaxKind::ModuleKw)
+    }
+
+    pub fn name(&self) -> Option<SyntaxToken> {
+        token(&self.0, SyntaxKind::Ident)
+    }
+
+    pub fn items(&self) -> impl Iterator<Item = Item> + '_ {
+        children(&self.0)
+    }
+}
+
+impl Usage {
+    pub fn docs(&self) -> impl Iterator<Item = SyntaxToken> + '_ {
+        docs(&self.0)
+    }
+
+    pub fn use_token(&self) -> Option<SyntaxToken> {
+        token(&self.0, SyntaxKind::UseKw)
+    }
+
+    pub fn path(&self) -> Option<Path> {
+        child(&self.0)
+    }
+}
+
+impl TypeDef {
+    pub fn docs(&self) -> impl Iterator<Item = SyntaxToken> + '_ {
+        docs(&self.0)
+    }
+
+    pub fn type_token(&self) -> Option<SyntaxToken> {
+        token(&self.0, SyntaxKind::TypeKw)
+    }
+
+    pub fn name(&self) -> Option<SyntaxToken> {
+        token(&self.0, SyntaxKind::Ident)
+    }
+
+    pub fn record(&self) -> Option<RecordDef> {
+        child(&self.0)
+    }
+
+    pub fn variants(&self) -> impl Iterator<Item = VariantDef> + '_ {
+        children(&self.0)
+    }
+}
+
+impl VariantDef {
+    pub fn name(&self) -> Option<SyntaxToken> {
+        token(&self.0, SyntaxKind::Ident)
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = Type> + '_ {
+        children(&self.0)
+    }
+}
+
+impl RecordDef {
+    pub fn fields(&self) -> impl Iterator<Item = FieldDef> + '_ {
+        children(&self.0)
+    }
+}
+
+impl FieldDef {
+    pub fn name(&self) -> Option<SyntaxToken> {
+        token(&self.0, SyntaxKind::Ident)
+    }
+
+    pub fn ty(&self) -> Option<Type> {
+        child(&self.0)
+    }
+}
+
+impl Path {
+    pub fn segments(&self) -> impl Iterator<Item = SyntaxToken> + '_ {
+        self.0
+            .tokens()
+            .filter(|token| token.kind() == SyntaxKind::Ident)
+    }
+}
+
+impl LetExpr {
+    pub fn pattern(&self) -> Option<Pattern> {
+        child(&self.0)
+    }
+
+    pub fn value(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+}
+
+impl EnsureExpr {
+    pub fn contract(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+}
+
+impl MatchExpr {
+    pub fn scrutinee(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+
+    pub fn arms(&self) -> impl Iterator<Item = MatchArm> + '_ {
+        children(&self.0)
+    }
+}
+
+impl IfExpr {
+    pub fn cond(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+
+    pub fn then_branch(&self) -> Option<Expr> {
+        children(&self.0).nth(1)
+    }
+
+    pub fn else_branch(&self) -> Option<Expr> {
+        children(&self.0).nth(2)
+    }
+}
+
+impl MatchArm {
+    pub fn pattern(&self) -> Option<Pattern> {
+        child(&self.0)
+    }
+
+    pub fn body(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+}
+
+impl AppExpr {
+    pub fn func(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+
+    pub fn args(&self) -> impl Iterator<Item = Expr> + '_ {
+        children(&self.0).skip(1)
+    }
+
+    /// The `<|` of an application that isn't juxtaposed
+    pub fn left_pipe(&self) -> Option<SyntaxToken> {
+        token(&self.0, SyntaxKind::LeftPipe)
+    }
+}
+
+impl PipeExpr {
+    pub fn arg(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+
+    pub fn func(&self) -> Option<Expr> {
+        children(&self.0).nth(1)
+    }
+}
+
+impl BinaryExpr {
+    pub fn lhs(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+
+    pub fn op(&self) -> Option<SyntaxToken> {
+        self.0.tokens().next()
+    }
+
+    pub fn rhs(&self) -> Option<Expr> {
+        children(&self.0).nth(1)
+    }
+}
+
+impl UnaryExpr {
+    pub fn op(&self) -> Option<SyntaxToken> {
+        self.0.tokens().next()
+    }
+
+    pub fn expr(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+}
+
+impl AscribedExpr {
+    pub fn expr(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+
+    pub fn ty(&self) -> Option<Type> {
+        child(&self.0)
+    }
+}
+
+impl LambdaExpr {
+    pub fn params(&self) -> impl Iterator<Item = Param> + '_ {
+        children(&self.0)
+    }
+
+    pub fn body(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+}
+
+impl ParenExpr {
+    pub fn expr(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+}
+
+impl TupleExpr {
+    pub fn elems(&self) -> impl Iterator<Item = Expr> + '_ {
+        children(&self.0)
+    }
+}
+
+impl ListExpr {
+    pub fn elems(&self) -> impl Iterator<Item = Expr> + '_ {
+        children(&self.0)
+    }
+}
+
+impl RecordExpr {
+    pub fn name(&self) -> Option<SyntaxToken> {
+        token(&self.0, SyntaxKind::Ident)
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = FieldInit> + '_ {
+        children(&self.0)
+    }
+}
+
+impl FieldInit {
+    pub fn name(&self) -> Option<SyntaxToken> {
+        token(&self.0, SyntaxKind::Ident)
+    }
+
+    pub fn value(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+}
+
+impl FieldExpr {
+    pub fn record(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+
+    pub fn field(&self) -> Option<SyntaxToken> {
+        token(&self.0, SyntaxKind::Ident)
+    }
+}
+
+impl LiteralExpr {
+    pub fn token(&self) -> Option<SyntaxToken> {
+        self.0.tokens().next()
+    }
+}
+
+impl VarExpr {
+    pub fn name(&self) -> Option<SyntaxToken> {
+        token(&self.0, SyntaxKind::Ident)
+    }
+}
+
+impl NamedType {
+    /// The type's name when it isn't a whole path
+    pub fn name(&self) -> Option<SyntaxToken> {
+        token(&self.0, SyntaxKind::Ident)
+    }
+
+    pub fn path(&self) -> Option<Path> {
+        child(&self.0)
+    }
+}
+
+impl TupleType {
+    pub fn elems(&self) -> impl Iterator<Item = Type> + '_ {
+        children(&self.0)
+    }
+}
+
+impl ListType {
+    pub fn elem(&self) -> Option<Type> {
+        child(&self.0)
+    }
+}
+
+impl FuncType {
+    pub fn args(&self) -> impl Iterator<Item = Type> + '_ {
+        children(&self.0)
+    }
+
+    pub fn ret(&self) -> Option<RetType> {
+        child(&self.0)
+    }
+}
+
+impl GenericType {
+    pub fn name(&self) -> Option<SyntaxToken> {
+        token(&self.0, SyntaxKind::Ident)
+    }
+}
+
+impl RefinedType {
+    pub fn binder(&self) -> Option<SyntaxToken> {
+        token(&self.0, SyntaxKind::Ident)
+    }
+
+    pub fn ty(&self) -> Option<Type> {
+        child(&self.0)
+    }
+
+    pub fn predicate(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+}
+
+impl ParenType {
+    pub fn ty(&self) -> Option<Type> {
+        child(&self.0)
+    }
+}
+
+impl LiteralPat {
+    pub fn token(&self) -> Option<SyntaxToken> {
+        self.0.tokens().next()
+    }
+}
+
+impl IdentPat {
+    pub fn name(&self) -> Option<SyntaxToken> {
+        token(&self.0, SyntaxKind::Ident)
+    }
+}
+
+impl PathPat {
+    pub fn path(&self) -> Option<Path> {
+        child(&self.0)
+    }
+}
+
+impl TuplePat {
+    pub fn elems(&self) -> impl Iterator<Item = Pattern> + '_ {
+        children(&self.0)
+    }
+}
+
+impl ParenPat {
+    pub fn pattern(&self) -> Option<Pattern> {
+        child(&self.0)
+    }
+}
+
+impl ConstructorPat {
+    pub fn name(&self) -> Option<SyntaxToken> {
+        token(&self.0, SyntaxKind::Ident)
+    }
+
+    pub fn args(&self) -> impl Iterator<Item = Pattern> + '_ {
+        children(&self.0)
+    }
+}